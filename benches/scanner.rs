@@ -0,0 +1,116 @@
+#![feature(test)]
+
+///! Scanner benchmark
+///!
+///! Compares the state machine scanner against the regex alternation
+///! it replaced, on test/test_files/parse_test.clj.
+///!
+///! Author Taketoshi Aono
+
+extern crate test;
+extern crate regex;
+extern crate risp;
+
+use regex::Regex;
+use test::Bencher;
+use risp::parser::loader;
+use risp::parser::literal_buffer::LiteralBuffer;
+use risp::parser::moduleinfo::ModuleInfo;
+use risp::parser::scanner::Scanner;
+use risp::parser::token::TokenKind;
+use risp::internal::heap::zone::ZoneAllocator;
+
+
+const FILE: &'static str = "test/test_files/parse_test.clj";
+
+
+/// The alternation used by the regex based scanner.
+const REGEXP: &'static str = r#"(?x)
+              (?P<comment>;[^\n\r]*)|
+              (?P<left_paren>\()|
+              (?P<right_paren>\))|
+              (?P<left_brace>\{)|
+              (?P<right_brace>\})|
+              (?P<left_bracket>\[)|
+              (?P<right_bracket>\])|
+              (?P<short_lambda>\#\()|
+              (?P<string>(?:")(?:\\.|[^"])*(?:"))|
+              (?P<quote_rm>')|
+              (?P<quote>quote(?:\s+))|
+              (?P<let>let(?:\s+))|
+              (?P<fn>fn(?:\s+))|
+              (?P<if>if(?:\s+))|
+              (?P<def>def(?:\s+))|
+              (?P<defmacro>defmacro(?:\s+))|
+              (?P<param_name>%(?:[1-9][0-9]*|&)?)|
+              (?P<int>-?[0-9]+)|
+              (?P<float>-?(?:[0-9]+(?:\.[0-9]+)?(?:[eE]-?[0-9]+)?|Infinity|NaN))|
+              (?P<hex>0[xX][0-9a-fA-F]+)|
+              (?P<bin>0[bB][10]+)|
+              (?P<long>-?[0-9]+(?:[lL]))|
+              (?P<bign>-?[0-9]+(?:[nN]))|
+              (?P<charu>\\u[0-9D-Fd-f][0-9a-fA-F]{3})|
+              (?P<lf>[\n\r])|
+              (?P<white_spaces>[\s\t]+)|
+              (?P<regexp>\#"(?:\\.|[^"])*")|
+              (?P<nil>nil)|
+              (?P<boolean>true|false)|
+              (?P<unquote_splicing>~@)|
+              (?P<deref>@)|
+              (?P<backtick>`)|
+              (?P<unquote>~)|
+              (?P<tag>\^)|
+              (?P<dispatch>\#[^"]+)|
+              (?P<keyword>:(?:[\./]|(?:[^\^`\#'"~@:%\(\)\[\]\{\}\n\r\t\s,](?:[^\^`\#'"~@:%\(\)\[\]\{\}\n\r\t\s,]|[0-9])*)))|
+              (?P<macro_keyword>::(?:[\./]|(?:[^\^`\#'"~@:%\(\)\[\]\{\}\n\r\t\s,](?:[^\^`\#'"~@:%\(\)\[\]\{\}\n\r\t\s,]|[0-9])*)))|
+              (?P<symbol>(?:[\./]|(?:[^\^`\#'"~@:%\(\)\[\]\{\}\n\r\t\s,](?:[^\^`\#'"~@:%\(\)\[\]\{\}\n\r\t\s\s,]|[0-9])*)))|
+              (?P<any>.)
+            "#;
+
+
+const GROUPS: &'static [&'static str] = &[
+    "lf", "comment", "white_spaces", "left_paren", "right_paren", "left_brace", "right_brace",
+    "left_bracket", "right_bracket", "int", "float", "hex", "bin", "long", "bign", "charu",
+    "string", "regexp", "nil", "boolean", "unquote_splicing", "deref", "quote_rm", "quote",
+    "backtick", "unquote", "tag", "dispatch", "keyword", "macro_keyword", "short_lambda",
+    "if", "fn", "let", "def", "defmacro", "symbol", "param_name", "any"];
+
+
+#[bench]
+fn bench_scan(b: &mut Bencher) {
+    let code = loader::load(FILE).unwrap();
+    let zone_allocator = ZoneAllocator::new();
+    let module_info = ModuleInfo::new(FILE);
+    let lb = LiteralBuffer::new(&zone_allocator);
+    b.bytes = code.len() as u64;
+    b.iter(|| {
+        let scanner = Scanner::new_code(&module_info, &code, &lb);
+        let mut count = 0;
+        while !scanner.scan().is(TokenKind::Eof) {
+            count += 1;
+        }
+        count
+    });
+}
+
+
+#[bench]
+fn bench_scan_regexp_baseline(b: &mut Bencher) {
+    let code = loader::load(FILE).unwrap();
+    let r = Regex::new(REGEXP).unwrap();
+    b.bytes = code.len() as u64;
+    b.iter(|| {
+        let mut index = 0;
+        let mut count = 0;
+        while let Some(cap) = r.captures(&code[index..]) {
+            for name in GROUPS {
+                if cap.name(name).is_some() {
+                    break;
+                }
+            }
+            index += cap.at(0).unwrap().len();
+            count += 1;
+        }
+        count
+    });
+}
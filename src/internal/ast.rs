@@ -31,8 +31,10 @@ use std::cell::{Ref, RefCell, RefMut, Cell};
 use std::collections::{HashMap};
 use parser::token::{TokenKind, Token};
 use parser::moduleinfo::ModuleInfo;
+use parser::sourceinfo::SourceInfo;
 use parser::literal_buffer::{LiteralBuffer};
 use internal::heap::zone::{ZoneAllocator, ZoneObject};
+use regex::Regex;
 
 
 pub enum Ast<'a> {
@@ -44,12 +46,17 @@ pub enum Ast<'a> {
     If(If<'a>),
     Quote(Quote<'a>),
     Def(Def<'a>),
+    Ns(Ns<'a>),
     Vector(Vector<'a>),
     Let(Let<'a>),
     Lambda(Lambda<'a>),
     DefMacro(DefMacro<'a>),
     LambdaSugar(LambdaSugar<'a>),
     Integer(Integer<'a>),
+    Long(Long<'a>),
+    BigInteger(BigInteger<'a>),
+    BigDecimal(BigDecimal<'a>),
+    Ratio(Ratio<'a>),
     Double(Double<'a>),
     String(String<'a>),
     UChar(UChar<'a>),
@@ -59,7 +66,8 @@ pub enum Ast<'a> {
     Boolean(Boolean<'a>),
     RegExp(RegExp<'a>),
     LambdaParam(LambdaParam<'a>),
-    Nil(Nil<'a>)
+    Nil(Nil<'a>),
+    Error(Error<'a>)
 }
 
 
@@ -82,6 +90,8 @@ pub trait AstVisitor<'a, T> {
 
     fn visit_def(&self, ast: &'a Ast<'a>) -> T;
 
+    fn visit_ns(&self, ast: &'a Ast<'a>) -> T;
+
     fn visit_vector(&self, ast: &'a Ast<'a>) -> T;
 
     fn visit_lambda(&self, ast: &'a Ast<'a>) -> T;
@@ -94,6 +104,14 @@ pub trait AstVisitor<'a, T> {
 
     fn visit_integer(&self, ast: &'a Ast<'a>) -> T;
 
+    fn visit_long(&self, ast: &'a Ast<'a>) -> T;
+
+    fn visit_big_integer(&self, ast: &'a Ast<'a>) -> T;
+
+    fn visit_big_decimal(&self, ast: &'a Ast<'a>) -> T;
+
+    fn visit_ratio(&self, ast: &'a Ast<'a>) -> T;
+
     fn visit_double(&self, ast: &'a Ast<'a>) -> T;
 
     fn visit_string(&self, ast: &'a Ast<'a>) -> T;
@@ -111,6 +129,8 @@ pub trait AstVisitor<'a, T> {
     fn visit_lambda_param(&self, ast: &'a Ast<'a>) -> T;
 
     fn visit_nil(&self, ast: &'a Ast<'a>) -> T;
+
+    fn visit_error(&self, ast: &'a Ast<'a>) -> T;
 }
 
 
@@ -130,12 +150,17 @@ impl<'a, 'v> Dispatcher<'a, 'v> for Ast<'a> {
             &Ast::Let(_) => visitor.visit_let(self),
             &Ast::If(_) => visitor.visit_if(self),
             &Ast::Def(_) => visitor.visit_def(self),
+            &Ast::Ns(_) => visitor.visit_ns(self),
             &Ast::DefMacro(_) => visitor.visit_defmacro(self),
             &Ast::Tag(_) => visitor.visit_tag(self),
             &Ast::Vector(_) => visitor.visit_vector(self),
             &Ast::Lambda(_) => visitor.visit_lambda(self),
             &Ast::LambdaSugar(_) => visitor.visit_lambda_sugar(self),
             &Ast::Integer(_) => visitor.visit_integer(self),
+            &Ast::Long(_) => visitor.visit_long(self),
+            &Ast::BigInteger(_) => visitor.visit_big_integer(self),
+            &Ast::BigDecimal(_) => visitor.visit_big_decimal(self),
+            &Ast::Ratio(_) => visitor.visit_ratio(self),
             &Ast::Double(_) => visitor.visit_double(self),
             &Ast::String(_) => visitor.visit_string(self),
             &Ast::UChar(_) => visitor.visit_uchar(self),
@@ -145,7 +170,8 @@ impl<'a, 'v> Dispatcher<'a, 'v> for Ast<'a> {
             &Ast::Boolean(_) => visitor.visit_boolean(self),
             &Ast::RegExp(_) => visitor.visit_regexp(self),
             &Ast::LambdaParam(_) => visitor.visit_lambda_param(self),
-            &Ast::Nil(_) => visitor.visit_nil(self)
+            &Ast::Nil(_) => visitor.visit_nil(self),
+            &Ast::Error(_) => visitor.visit_error(self)
         }
     }
 }
@@ -162,12 +188,17 @@ macro_rules! ast_name {
             &Ast::If(_) => "If",
             &Ast::Let(_) => "Let",
             &Ast::Def(_) => "Def",
+            &Ast::Ns(_) => "Ns",
             &Ast::Tag(_) => "Tag",
             &Ast::Vector(_) => "Vector",
             &Ast::Lambda(_) => "Lambda",
             &Ast::DefMacro(_) => "DefMacro",
             &Ast::LambdaSugar(_) => "Lambda",
             &Ast::Integer(_) => "Integer",
+            &Ast::Long(_) => "Long",
+            &Ast::BigInteger(_) => "BigInteger",
+            &Ast::BigDecimal(_) => "BigDecimal",
+            &Ast::Ratio(_) => "Ratio",
             &Ast::Double(_) => "Double",
             &Ast::String(_) => "String",
             &Ast::UChar(_) => "UChar",
@@ -177,7 +208,8 @@ macro_rules! ast_name {
             &Ast::Boolean(_) => "Boolean",
             &Ast::RegExp(_) => "RegExp",
             &Ast::LambdaParam(_) => "LambdaParam",
-            &Ast::Nil(_) => "Nil"
+            &Ast::Nil(_) => "Nil",
+            &Ast::Error(_) => "Error"
         }
     }
 }
@@ -237,6 +269,8 @@ macro_rules! has_parent_impl {
 
 pub trait HasToken<'a> {
     fn token(&self) -> Token<'a>;
+
+    fn set_token(&self, token: Token<'a>);
 }
 
 
@@ -244,7 +278,34 @@ macro_rules! has_token_impl {
     ($t:ty) => {
         impl<'a> HasToken<'a> for $t {
             fn token(&self) -> Token<'a> {
-                self.token
+                self.token.get()
+            }
+
+            fn set_token(&self, token: Token<'a>) {
+                self.token.set(token);
+            }
+        }
+    }
+}
+
+
+/// Metadata attached by the ^ reader macro, a Map of the metadata entries.
+pub trait HasMeta<'a> {
+    fn meta(&self) -> Option<&'a Ast<'a>>;
+
+    fn set_meta(&self, meta: &'a Ast<'a>);
+}
+
+
+macro_rules! has_meta_impl {
+    ($t:ty) => {
+        impl<'a> HasMeta<'a> for $t {
+            fn meta(&self) -> Option<&'a Ast<'a>> {
+                self.meta.get()
+            }
+
+            fn set_meta(&self, meta: &'a Ast<'a>) {
+                self.meta.set(Some(meta));
             }
         }
     }
@@ -256,6 +317,7 @@ macro_rules! generic_impl {
         has_children_impl!($t);
         has_parent_impl!($t);
         has_token_impl!($t);
+        has_meta_impl!($t);
     }
 }
 
@@ -264,6 +326,7 @@ macro_rules! literal_impl {
     ($t:ty) => {
         has_parent_impl!($t);
         has_token_impl!($t);
+        has_meta_impl!($t);
     }
 }
 
@@ -279,12 +342,17 @@ macro_rules! literal_unwrap {
             &Ast::Let(ref a) => Some(a as &$t),
             &Ast::If(ref a) => Some(a as &$t),
             &Ast::Def(ref a) => Some(a as &$t),
+            &Ast::Ns(ref a) => Some(a as &$t),
             &Ast::Vector(ref a) => Some(a as &$t),
             &Ast::ModuleReference(ref a) => Some(a as &$t),
             &Ast::Lambda(ref a) => Some(a as &$t),
             &Ast::DefMacro(ref a) => Some(a as &$t),
             &Ast::LambdaSugar(ref a) => Some(a as &$t),
             &Ast::Integer(ref a) => Some(a as &$t),
+            &Ast::Long(ref a) => Some(a as &$t),
+            &Ast::BigInteger(ref a) => Some(a as &$t),
+            &Ast::BigDecimal(ref a) => Some(a as &$t),
+            &Ast::Ratio(ref a) => Some(a as &$t),
             &Ast::Double(ref a) => Some(a as &$t),
             &Ast::String(ref a) => Some(a as &$t),
             &Ast::Symbol(ref a) => Some(a as &$t),
@@ -294,6 +362,7 @@ macro_rules! literal_unwrap {
             &Ast::RegExp(ref a) => Some(a as &$t),
             &Ast::LambdaParam(ref a) => Some(a as &$t),
             &Ast::Nil(ref a) => Some(a as &$t),
+            &Ast::Error(ref a) => Some(a as &$t),
             &Ast::Module(ref m) => None
         }
     }
@@ -311,6 +380,12 @@ macro_rules! unwrap_has_token {
     }
 }
 
+macro_rules! unwrap_has_meta {
+    ($ast:expr) => {
+        literal_unwrap!($ast, HasMeta)
+    }
+}
+
 
 
 macro_rules! unwrap_has_children {
@@ -457,150 +532,228 @@ has_children_impl!(Module<'a>);
 
 
 pub struct List<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     children: RefCell<Vec<&'a Ast<'a>>>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 generic_impl!(List<'a>);
 
 
 pub struct If<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     cond: Cell<Option<&'a Ast<'a>>>,
     then_body: Cell<Option<&'a Ast<'a>>>,
     else_body: Cell<Option<&'a Ast<'a>>>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(If<'a>);
 
 
 pub struct Def<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     name: Cell<Option<&'a Ast<'a>>>,
     expr: Cell<Option<&'a Ast<'a>>>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(Def<'a>);
 
 
+/// ns form, the references are the `(:require ...)` and `(:import ...)` clauses as read.
+pub struct Ns<'a> {
+    token: Cell<Token<'a>>,
+    name: &'a Ast<'a>,
+    references: RefCell<Vec<&'a Ast<'a>>>,
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
+}
+literal_impl!(Ns<'a>);
+
+
 pub struct Quote<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     expr: Cell<Option<&'a Ast<'a>>>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(Quote<'a>);
 
 
 pub struct Tag<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     children: RefCell<Vec<&'a Ast<'a>>>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 generic_impl!(Tag<'a>);
 
 
 pub struct Map<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     children: RefCell<Vec<&'a Ast<'a>>>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 generic_impl!(Map<'a>);
 
 
 pub struct ModuleReference<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     children: RefCell<Vec<&'a Ast<'a>>>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 generic_impl!(ModuleReference<'a>);
 
 
 pub struct Set<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     children: RefCell<Vec<&'a Ast<'a>>>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 generic_impl!(Set<'a>);
 
 
 pub struct Vector<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     children: RefCell<Vec<&'a Ast<'a>>>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 generic_impl!(Vector<'a>);
 
 
 pub struct Let<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     body: RefCell<Vec<&'a Ast<'a>>>,
     bindings: RefCell<Vec<(&'a Ast<'a>, &'a Ast<'a>)>>,
     scope: &'a Scope<'a>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(Let<'a>);
 
 
+/// fn form, a multi-arity fn has no parameters and body but the clauses,
+/// each of which is a Lambda of single arity.
 pub struct Lambda<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
+    name: Cell<Option<&'a Ast<'a>>>,
+    clauses: RefCell<Vec<&'a Ast<'a>>>,
     arguments: RefCell<Vec<&'a Ast<'a>>>,
+    rest: Cell<Option<&'a Ast<'a>>>,
     body: RefCell<Vec<&'a Ast<'a>>>,
     scope: &'a Scope<'a>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(Lambda<'a>);
 
 
 pub struct DefMacro<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     name: &'a Ast<'a>,
     arguments: RefCell<Vec<&'a Ast<'a>>>,
+    rest: Cell<Option<&'a Ast<'a>>>,
     body: RefCell<Vec<&'a Ast<'a>>>,
     scope: &'a Scope<'a>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(DefMacro<'a>);
 
 
+/// #(...) literal, the only child is the Lambda which the literal is desugared into.
 pub struct LambdaSugar<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     children: RefCell<Vec<&'a Ast<'a>>>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    arity: Cell<i32>,
+    variadic: Cell<bool>,
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 generic_impl!(LambdaSugar<'a>);
 
 
 pub struct Integer<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     value: i32,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(Integer<'a>);
 
 
+pub struct Long<'a> {
+    token: Cell<Token<'a>>,
+    value: i64,
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
+}
+literal_impl!(Long<'a>);
+
+
+/// Arbitrary precision integer, the value is kept as a canonical decimal string.
+pub struct BigInteger<'a> {
+    token: Cell<Token<'a>>,
+    value: &'a str,
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
+}
+literal_impl!(BigInteger<'a>);
+
+
+/// Arbitrary precision decimal, the value is kept as written without the M suffix.
+pub struct BigDecimal<'a> {
+    token: Cell<Token<'a>>,
+    value: &'a str,
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
+}
+literal_impl!(BigDecimal<'a>);
+
+
+/// Ratio reduced to lowest terms, the denominator is always greater than 1.
+pub struct Ratio<'a> {
+    token: Cell<Token<'a>>,
+    numerator: i64,
+    denominator: i64,
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
+}
+literal_impl!(Ratio<'a>);
+
+
 
 pub struct Double<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     value: f64,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(Double<'a>);
 
 
 
 pub struct String<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     value: &'a str,
-    parent: Cell<Option<&'a Ast<'a>>>
+    raw: &'a str,
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(String<'a>);
 
 
 
 pub struct UChar<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     value: i32,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(UChar<'a>);
 
@@ -619,60 +772,84 @@ pub enum SymbolMode {
     Parameter {
         index: i32,
         depth: SymbolDepth
+    },
+    /// The parameter following `&`, the index is the number of the fixed parameters.
+    RestParameter {
+        index: i32,
+        depth: SymbolDepth
     }
 }
 
 
 pub struct Symbol<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     value: &'a str,
     bound: Cell<Option<&'a Ast<'a>>>,
     mode: Cell<SymbolMode>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(Symbol<'a>);
 
 
 pub struct Keyword<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     value: &'a str,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(Keyword<'a>);
 
 
 
 pub struct Boolean<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     value: bool,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(Boolean<'a>);
 
 
+/// Regular expression literal, the pattern is compiled while parsing.
 pub struct RegExp<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     value: &'a str,
-    parent: Cell<Option<&'a Ast<'a>>>
+    regex: Regex,
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(RegExp<'a>);
 
 
 pub struct LambdaParam<'a> {
-    token: Token<'a>,
+    token: Cell<Token<'a>>,
     value: i32,
-    parent: Cell<Option<&'a Ast<'a>>>
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(LambdaParam<'a>);
 
 
 pub struct Nil<'a> {
-    token: Token<'a>,
-    parent: Cell<Option<&'a Ast<'a>>>
+    token: Cell<Token<'a>>,
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
 }
 literal_impl!(Nil<'a>);
 
 
+/// Placeholder of the form which could not be parsed,
+/// the parser puts it in the partial Ast and continues after the form.
+pub struct Error<'a> {
+    token: Cell<Token<'a>>,
+    message: std::string::String,
+    parent: Cell<Option<&'a Ast<'a>>>,
+    meta: Cell<Option<&'a Ast<'a>>>
+}
+literal_impl!(Error<'a>);
+
+
 impl<'a> ZoneObject<Ast<'a>> for Ast<'a> {}
 
 
@@ -688,218 +865,313 @@ impl<'a> Ast<'a> {
 
     pub fn new_list(za: &'a ZoneAllocator, token: Token<'a>) -> &'a Ast<'a> {
         za.alloc(Ast::List(List {
-            token: token,
+            token: Cell::new(token),
             children: RefCell::new(Vec::new()),
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
     pub fn new_if(za: &'a ZoneAllocator, token: Token<'a>) -> &'a Ast<'a> {
         za.alloc(Ast::If(If {
-            token: token,
+            token: Cell::new(token),
             cond: Cell::new(None),
             then_body: Cell::new(None),
             else_body: Cell::new(None),
-            parent: Cell::new(None)
+            parent: Cell::new(None),
+            meta: Cell::new(None)
         }))
     }
 
 
     pub fn new_quote(za: &'a ZoneAllocator, token: Token<'a>) -> &'a Ast<'a> {
         za.alloc(Ast::Quote(Quote {
-            token: token,
+            token: Cell::new(token),
             expr: Cell::new(Option::None),
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
     
 
     pub fn new_def(za: &'a ZoneAllocator, token: Token<'a>) -> &'a Ast<'a> {
         za.alloc(Ast::Def(Def {
-            token: token,
+            token: Cell::new(token),
             name: Cell::new(None),
             expr: Cell::new(None),
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
     pub fn new_tag(za: &'a ZoneAllocator, token: Token<'a>) -> &'a Ast<'a> {
         za.alloc(Ast::Tag(Tag {
-            token: token,
+            token: Cell::new(token),
             children: RefCell::new(Vec::new()),
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
     pub fn new_map(za: &'a ZoneAllocator, token: Token<'a>) -> &'a Ast<'a> {
         za.alloc(Ast::Map(Map {
-            token: token,
+            token: Cell::new(token),
             children: RefCell::new(Vec::new()),
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
     pub fn new_set(za: &'a ZoneAllocator, token: Token<'a>) -> &'a Ast<'a> {
         za.alloc(Ast::Set(Set {
-            token: token,
+            token: Cell::new(token),
             children: RefCell::new(Vec::new()),
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
     pub fn new_vector(za: &'a ZoneAllocator, token: Token<'a>) -> &'a Ast<'a> {
         za.alloc(Ast::Vector(Vector {
-            token: token,
+            token: Cell::new(token),
             children: RefCell::new(Vec::new()),
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
     pub fn new_module_reference(za: &'a ZoneAllocator, token: Token<'a>) -> &'a Ast<'a> {
         za.alloc(Ast::ModuleReference(ModuleReference {
-            token: token,
+            token: Cell::new(token),
             children: RefCell::new(Vec::new()),
-            parent: Cell::new(None)
+            parent: Cell::new(None),
+            meta: Cell::new(None)
         }))
     }
 
 
     pub fn new_let(za: &'a ZoneAllocator, token: Token<'a>, scope: &'a Scope<'a>) -> &'a Ast<'a> {
         za.alloc(Ast::Let(Let {
-            token: token,
+            token: Cell::new(token),
             bindings: RefCell::new(Vec::new()),
             body: RefCell::new(Vec::new()),
             scope: scope,
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
     pub fn new_lambda(za: &'a ZoneAllocator, token: Token<'a>, scope: &'a Scope<'a>) -> &'a Ast<'a> {
         za.alloc(Ast::Lambda(Lambda {
-            token: token,
+            token: Cell::new(token),
+            name: Cell::new(Option::None),
+            clauses: RefCell::new(Vec::new()),
             arguments: RefCell::new(Vec::new()),
+            rest: Cell::new(Option::None),
             body: RefCell::new(Vec::new()),
             scope: scope,
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
+        }))
+    }
+
+
+    pub fn new_ns(za: &'a ZoneAllocator, token: Token<'a>, name: &'a Ast<'a>) -> &'a Ast<'a> {
+        za.alloc(Ast::Ns(Ns {
+            token: Cell::new(token),
+            name: name,
+            references: RefCell::new(Vec::new()),
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
     pub fn new_defmacro(za: &'a ZoneAllocator, token: Token<'a>, name: &'a Ast<'a>, scope: &'a Scope<'a>) -> &'a Ast<'a> {
         za.alloc(Ast::DefMacro(DefMacro {
-            token: token,
+            token: Cell::new(token),
             name: name,
             arguments: RefCell::new(Vec::new()),
+            rest: Cell::new(Option::None),
             body: RefCell::new(Vec::new()),
             scope: scope,
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
     pub fn new_lambda_sugar(za: &'a ZoneAllocator, token: Token<'a>) -> &'a Ast<'a> {
         za.alloc(Ast::LambdaSugar(LambdaSugar {
-            token: token,
+            token: Cell::new(token),
             children: RefCell::new(Vec::new()),
-            parent: Cell::new(Option::None)
+            arity: Cell::new(0),
+            variadic: Cell::new(false),
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
     
 
     pub fn new_integer(za: &'a ZoneAllocator, token: Token<'a>, value: i32) -> &'a Ast<'a> {
         za.alloc(Ast::Integer(Integer {
-            token: token,
+            token: Cell::new(token),
+            value: value,
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
+        }))
+    }
+
+
+    pub fn new_long(za: &'a ZoneAllocator, token: Token<'a>, value: i64) -> &'a Ast<'a> {
+        za.alloc(Ast::Long(Long {
+            token: Cell::new(token),
+            value: value,
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
+        }))
+    }
+
+
+    pub fn new_big_integer(za: &'a ZoneAllocator, token: Token<'a>, value: &'a str) -> &'a Ast<'a> {
+        za.alloc(Ast::BigInteger(BigInteger {
+            token: Cell::new(token),
+            value: value,
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
+        }))
+    }
+
+
+    pub fn new_big_decimal(za: &'a ZoneAllocator, token: Token<'a>, value: &'a str) -> &'a Ast<'a> {
+        za.alloc(Ast::BigDecimal(BigDecimal {
+            token: Cell::new(token),
             value: value,
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
+        }))
+    }
+
+
+    pub fn new_ratio(za: &'a ZoneAllocator, token: Token<'a>, numerator: i64, denominator: i64) -> &'a Ast<'a> {
+        za.alloc(Ast::Ratio(Ratio {
+            token: Cell::new(token),
+            numerator: numerator,
+            denominator: denominator,
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
     pub fn new_double(za: &'a ZoneAllocator, token: Token<'a>, value: f64) -> &'a Ast<'a> {
         za.alloc(Ast::Double(Double {
-            token: token,
+            token: Cell::new(token),
             value: value,
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
-    pub fn new_string(za: &'a ZoneAllocator, token: Token<'a>, value: &'a str) -> &'a Ast<'a> {
+    /// `value` is the decoded string and `raw` is the text between the quotes.
+    pub fn new_string(za: &'a ZoneAllocator, token: Token<'a>, value: &'a str, raw: &'a str) -> &'a Ast<'a> {
         za.alloc(Ast::String(String {
-            token: token,
+            token: Cell::new(token),
             value: value,
-            parent: Cell::new(Option::None)
+            raw: raw,
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
     pub fn new_symbol(za: &'a ZoneAllocator, token: Token<'a>, value: &'a str, mode: SymbolMode) -> &'a Ast<'a> {
         za.alloc(Ast::Symbol(Symbol {
-            token: token,
+            token: Cell::new(token),
             value: value,
             mode: Cell::new(mode),
             bound: Cell::new(None),
-            parent: Cell::new(None)
+            parent: Cell::new(None),
+            meta: Cell::new(None)
         }))
     }
 
 
     pub fn new_uchar(za: &'a ZoneAllocator, token: Token<'a>, value: i32) -> &'a Ast<'a> {
         za.alloc(Ast::UChar(UChar {
-            token: token,
+            token: Cell::new(token),
             value: value,
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
     pub fn new_keyword(za: &'a ZoneAllocator, token: Token<'a>, value: &'a str) -> &'a Ast<'a> {
         za.alloc(Ast::Keyword(Keyword {
-            token: token,
+            token: Cell::new(token),
             value: value,
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
     pub fn new_boolean(za: &'a ZoneAllocator, token: Token<'a>, value: bool) -> &'a Ast<'a> {
         za.alloc(Ast::Boolean(Boolean {
-            token: token,
+            token: Cell::new(token),
             value: value,
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
-    pub fn new_regexp(za: &'a ZoneAllocator, token: Token<'a>, value: &'a str) -> &'a Ast<'a> {
+    /// `value` is the pattern without the #" and " delimiters.
+    pub fn new_regexp(za: &'a ZoneAllocator, token: Token<'a>, value: &'a str, regex: Regex) -> &'a Ast<'a> {
         za.alloc(Ast::RegExp(RegExp {
-            token: token,
+            token: Cell::new(token),
             value: value,
-            parent: Cell::new(Option::None)
+            regex: regex,
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
 
     pub fn new_lambda_param(za: &'a ZoneAllocator, token: Token<'a>, value: i32) -> &'a Ast<'a> {
         za.alloc(Ast::LambdaParam(LambdaParam {
-            token: token,
+            token: Cell::new(token),
             value: value,
-            parent: Cell::new(Option::None)
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
     
     pub fn new_nil(za: &'a ZoneAllocator, token: Token<'a>) -> &'a Ast<'a> {
         za.alloc(Ast::Nil(Nil {
-            token: token,
-            parent: Cell::new(Option::None)
+            token: Cell::new(token),
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
+        }))
+    }
+
+
+    pub fn new_error(za: &'a ZoneAllocator, token: Token<'a>, message: &str) -> &'a Ast<'a> {
+        za.alloc(Ast::Error(Error {
+            token: Cell::new(token),
+            message: message.to_string(),
+            parent: Cell::new(Option::None),
+            meta: Cell::new(Option::None)
         }))
     }
 
@@ -1066,6 +1338,32 @@ impl<'a> Ast<'a> {
     }
 
 
+    pub fn ns_name(&self) -> Option<&'a Ast<'a>> {
+        match self {
+            &Ast::Ns(ref n) => Option::Some(n.name),
+            _ => Option::None
+        }
+    }
+
+
+    pub fn add_ns_reference(&self, reference: &'a Ast<'a>) {
+        match self {
+            &Ast::Ns(ref n) => {
+                n.references.borrow_mut().push(reference);
+            },
+            _ => {panic!("add_ns_reference called to non ns ast.");}
+        }
+    }
+
+
+    pub fn ns_references(&self) -> Option<Ref<Vec<&'a Ast<'a>>>> {
+        match self {
+            &Ast::Ns(ref n) => Option::Some(n.references.borrow()),
+            _ => Option::None
+        }
+    }
+
+
     pub fn add_macro_body(&self, body: &'a Ast<'a>) {
         match self {
             &Ast::DefMacro(ref d) => {
@@ -1076,6 +1374,64 @@ impl<'a> Ast<'a> {
     }
 
 
+    pub fn macro_arguments(&self) -> Option<Ref<Vec<&'a Ast<'a>>>> {
+        match self {
+            &Ast::DefMacro(ref d) => {
+                Option::Some(d.arguments.borrow())
+            },
+            _ => Option::None
+        }
+    }
+
+
+    pub fn macro_body(&self) -> Option<Ref<Vec<&'a Ast<'a>>>> {
+        match self {
+            &Ast::DefMacro(ref d) => {
+                Option::Some(d.body.borrow())
+            },
+            _ => Option::None
+        }
+    }
+
+
+    /// Record the arity inferred from the highest %n and whether %& is used.
+    pub fn set_lambda_sugar_arity(&self, arity: i32, variadic: bool) {
+        match self {
+            &Ast::LambdaSugar(ref l) => {
+                l.arity.set(arity);
+                l.variadic.set(variadic);
+            },
+            _ => {panic!("set_lambda_sugar_arity called to non lambda sugar ast.");}
+        }
+    }
+
+
+    /// The number of the fixed parameters of #(...).
+    pub fn lambda_sugar_arity(&self) -> Option<i32> {
+        match self {
+            &Ast::LambdaSugar(ref l) => Option::Some(l.arity.get()),
+            _ => Option::None
+        }
+    }
+
+
+    pub fn lambda_sugar_variadic(&self) -> Option<bool> {
+        match self {
+            &Ast::LambdaSugar(ref l) => Option::Some(l.variadic.get()),
+            _ => Option::None
+        }
+    }
+
+
+    /// The Lambda which #(...) is desugared into.
+    pub fn desugared_lambda(&self) -> Option<&'a Ast<'a>> {
+        match self {
+            &Ast::LambdaSugar(ref l) => l.children.borrow().first().map(|a| *a),
+            _ => Option::None
+        }
+    }
+
+
     pub fn add_let_binding(&self, binding: (&'a Ast<'a>, &'a Ast<'a>)) {
         match self {
             &Ast::Let(ref d) => {
@@ -1096,6 +1452,16 @@ impl<'a> Ast<'a> {
     }
 
 
+    pub fn let_bindings(&self) -> Ref<Vec<(&'a Ast<'a>, &'a Ast<'a>)>> {
+        match self {
+            &Ast::Let(ref d) => {
+                d.bindings.borrow()
+            },
+            _ => {panic!("let_bindings called to non let ast.");}
+        }
+    }
+
+
     pub fn let_body(&self) -> Ref<Vec<&'a Ast<'a>>> {
         match self {
             &Ast::Let(ref d) => {
@@ -1176,7 +1542,84 @@ impl<'a> Ast<'a> {
     }
 
 
-    pub fn lambda_arguments(&self, arg: &'a Ast<'a>) -> Option<Ref<Vec<&'a Ast<'a>>>> {
+    /// Set the parameter following `&` of the fn or defmacro.
+    pub fn set_rest_parameter(&self, rest: &'a Ast<'a>) {
+        match self {
+            &Ast::Lambda(ref l) => l.rest.set(Some(rest)),
+            &Ast::DefMacro(ref d) => d.rest.set(Some(rest)),
+            _ => {panic!("set_rest_parameter called to non lambda ast.");}
+        }
+    }
+
+
+    pub fn rest_parameter(&self) -> Option<&'a Ast<'a>> {
+        match self {
+            &Ast::Lambda(ref l) => l.rest.get(),
+            &Ast::DefMacro(ref d) => d.rest.get(),
+            _ => Option::None
+        }
+    }
+
+
+    /// The number of the fixed parameters of the fn or defmacro and whether it is variadic,
+    /// None for the multi-arity fn, see `lambda_clauses`.
+    pub fn arity(&self) -> Option<(usize, bool)> {
+        match self {
+            &Ast::Lambda(ref l) => {
+                if !l.clauses.borrow().is_empty() {
+                    return Option::None;
+                }
+                Option::Some((l.arguments.borrow().len(), l.rest.get().is_some()))
+            }
+            &Ast::DefMacro(ref d) => Option::Some((d.arguments.borrow().len(), d.rest.get().is_some())),
+            _ => Option::None
+        }
+    }
+
+
+    /// Set the name of `(fn name [...] ...)`, which refers to the fn itself.
+    pub fn set_lambda_name(&self, name: &'a Ast<'a>) {
+        match self {
+            &Ast::Lambda(ref l) => l.name.set(Some(name)),
+            _ => {panic!("set_lambda_name called to non lambda ast.");}
+        }
+    }
+
+
+    pub fn lambda_name(&self) -> Option<&'a Ast<'a>> {
+        match self {
+            &Ast::Lambda(ref l) => l.name.get(),
+            _ => Option::None
+        }
+    }
+
+
+    pub fn add_lambda_clause(&self, clause: &'a Ast<'a>) {
+        match self {
+            &Ast::Lambda(ref l) => {
+                l.clauses.borrow_mut().push(clause);
+            },
+            _ => {panic!("add_lambda_clause called to non lambda ast.");}
+        }
+    }
+
+
+    /// The arity clauses of the fn, the fn itself if it has single arity.
+    pub fn lambda_clauses(&'a self) -> Option<Vec<&'a Ast<'a>>> {
+        match self {
+            &Ast::Lambda(ref l) => {
+                let clauses = l.clauses.borrow();
+                if clauses.is_empty() {
+                    return Option::Some(vec![self]);
+                }
+                Option::Some(clauses.clone())
+            },
+            _ => Option::None
+        }
+    }
+
+
+    pub fn lambda_arguments(&self) -> Option<Ref<Vec<&'a Ast<'a>>>> {
         match self {
             &Ast::Lambda(ref l) => {
                 Option::Some(l.arguments.borrow())
@@ -1186,7 +1629,7 @@ impl<'a> Ast<'a> {
     }
 
 
-    pub fn lambda_body(&self, body: &'a Ast<'a>) -> Option<Ref<Vec<&'a Ast<'a>>>> {
+    pub fn lambda_body(&self) -> Option<Ref<Vec<&'a Ast<'a>>>> {
         match self {
             &Ast::Lambda(ref l) => {
                 Option::Some(l.body.borrow())
@@ -1242,6 +1685,80 @@ impl<'a> Ast<'a> {
             None => None
         }
     }
+
+
+    /// The metadata map of the form, None if no metadata is attached.
+    pub fn meta(&self) -> Option<&'a Ast<'a>> {
+        match unwrap_has_meta!(self) {
+            Some(a) => a.meta(),
+            None => None
+        }
+    }
+
+
+    pub fn set_meta(&self, meta: &'a Ast<'a>) {
+        match unwrap_has_meta!(self) {
+            Some(a) => a.set_meta(meta),
+            None => {panic!("Module can not have metadata.");}
+        }
+    }
+
+
+    /// The metadata value of the keyword `key`, e.g. `meta_value(":doc")`.
+    pub fn meta_value(&self, key: &str) -> Option<&'a Ast<'a>> {
+        match self.meta() {
+            Some(meta) => {
+                let entries = meta.children().unwrap();
+                for pair in entries.chunks(2) {
+                    match pair[0] {
+                        &Ast::Keyword(ref k) if k.value == key => {return Some(pair[1]);}
+                        _ => {}
+                    }
+                }
+                None
+            }
+            None => None
+        }
+    }
+
+
+    /// The docstring of def, defn or defmacro, the `:doc` metadata.
+    pub fn docstring(&self) -> Option<&'a str> {
+        match self.meta_value(":doc") {
+            Some(doc) => doc.string_value(),
+            None => None
+        }
+    }
+
+
+    /// Whether the var is private, defined by defn- or with the `:private` metadata.
+    pub fn is_private(&self) -> bool {
+        match self.meta_value(":private") {
+            Some(private) => private.boolean_value().unwrap_or(false),
+            None => false
+        }
+    }
+
+
+    /// Source range of the whole form.
+    pub fn source_info(&self) -> Option<SourceInfo<'a>> {
+        match unwrap_has_token!(self) {
+            Some(a) => Some(a.token().info()),
+            None => None
+        }
+    }
+
+
+    /// Stretch the source range of the form until the end of `info`.
+    pub fn extend_source_info(&self, info: SourceInfo<'a>) {
+        match unwrap_has_token!(self) {
+            Some(a) => {
+                let token = a.token();
+                a.set_token(token.extend(info));
+            },
+            None => {}
+        }
+    }
     
 
     pub fn string_value(&self) -> Option<&'a str> {
@@ -1254,6 +1771,48 @@ impl<'a> Ast<'a> {
     }
 
 
+    /// The compiled regex of the regexp literal.
+    pub fn regex_value(&self) -> Option<&Regex> {
+        match self {
+            &Ast::RegExp(ref a) => Option::Some(&a.regex),
+            _ => Option::None
+        }
+    }
+
+
+    /// The namespace and the name of the qualified symbol, the namespace is resolved
+    /// from the alias, e.g. `str/join` is `("clojure.string", "join")`.
+    pub fn qualified_name(&self) -> Option<(&'a str, &'a str)> {
+        match self {
+            &Ast::ModuleReference(ref m) => {
+                let children = m.children.borrow();
+                match (children[0].symbol_value(), children[1].symbol_value()) {
+                    (Some(namespace), Some(name)) => Option::Some((namespace, name)),
+                    _ => Option::None
+                }
+            }
+            _ => Option::None
+        }
+    }
+
+
+    pub fn symbol_value(&self) -> Option<&'a str> {
+        match self {
+            &Ast::Symbol(ref a) => Option::Some(a.value),
+            _ => Option::None
+        }
+    }
+
+
+    /// The string literal as written in the source, escape sequences are not decoded.
+    pub fn raw_string_value(&self) -> Option<&'a str> {
+        match self {
+            &Ast::String(ref a) => Option::Some(a.raw),
+            _ => Option::None
+        }
+    }
+
+
     pub fn int_value(&self) -> Option<i32> {
         match self {
             &Ast::Integer(ref i) => Option::Some(i.value),
@@ -1262,6 +1821,40 @@ impl<'a> Ast<'a> {
     }
 
 
+    pub fn long_value(&self) -> Option<i64> {
+        match self {
+            &Ast::Long(ref l) => Option::Some(l.value),
+            _ => Option::None
+        }
+    }
+
+
+    /// Decimal digits of BigInteger or BigDecimal.
+    pub fn big_number_value(&self) -> Option<&'a str> {
+        match self {
+            &Ast::BigInteger(ref b) => Option::Some(b.value),
+            &Ast::BigDecimal(ref b) => Option::Some(b.value),
+            _ => Option::None
+        }
+    }
+
+
+    pub fn ratio_value(&self) -> Option<(i64, i64)> {
+        match self {
+            &Ast::Ratio(ref r) => Option::Some((r.numerator, r.denominator)),
+            _ => Option::None
+        }
+    }
+
+
+    pub fn uchar_value(&self) -> Option<i32> {
+        match self {
+            &Ast::UChar(ref c) => Option::Some(c.value),
+            _ => Option::None
+        }
+    }
+
+
     pub fn double_value(&self) -> Option<f64> {
         match self {
             &Ast::Double(ref d) => Option::Some(d.value),
@@ -1286,6 +1879,31 @@ impl<'a> Ast<'a> {
     }
 
 
+    pub fn is_map(&self) -> bool {
+        match self {
+            &Ast::Map(_) => true,
+            _ => false
+        }
+    }
+
+
+    pub fn is_error(&self) -> bool {
+        match self {
+            &Ast::Error(_) => true,
+            _ => false
+        }
+    }
+
+
+    /// The message of the parse error which the Error node stands for.
+    pub fn error_message(&self) -> Option<&str> {
+        match self {
+            &Ast::Error(ref e) => Option::Some(&e.message),
+            _ => Option::None
+        }
+    }
+
+
     pub fn to_string_tree(&self) -> std::string::String {
         self.to_string_tree_helper("".to_string())
     }
@@ -1321,6 +1939,14 @@ impl<'a> Ast<'a> {
                         }
                         base
                     }
+                    &Ast::Ns(ref n) => {
+                        let mut base = format!("{}{}", indent, ast_name!(self));
+                        base = format!("{}\n{}", base, n.name.to_string_tree_helper(format!("  {}", indent)));
+                        for reference in n.references.borrow().iter() {
+                            base = format!("{}\n{}", base, reference.to_string_tree_helper(format!("  {}", indent)));
+                        }
+                        base
+                    }
                     &Ast::Let(ref l) => {
                         let mut base = format!("{}{}({})", indent, ast_name!(self), l.scope);
                         base = format!("{}\n{}  *Bindings", base, indent);
@@ -1337,10 +1963,32 @@ impl<'a> Ast<'a> {
                     }
                     &Ast::Lambda(ref l) => {
                         let mut base = format!("{}{}({})", indent, ast_name!(self), l.scope);
+                        match l.name.get() {
+                            Some(name) => {
+                                base = format!("{}\n{}  *Name", base, indent);
+                                base = format!("{}\n{}", base, name.to_string_tree_helper(format!("    {}", indent)));
+                            }
+                            None => {}
+                        }
+                        let clauses = l.clauses.borrow();
+                        if !clauses.is_empty() {
+                            base = format!("{}\n{}  *Clauses", base, indent);
+                            for clause in clauses.iter() {
+                                base = format!("{}\n{}", base, clause.to_string_tree_helper(format!("    {}", indent)));
+                            }
+                            return base;
+                        }
                         base = format!("{}\n{}  *Parameters", base, indent);
                         for args in l.arguments.borrow().iter() {
                             base = format!("{}\n{}", base, args.to_string_tree_helper(format!("    {}", indent)));
                         }
+                        match l.rest.get() {
+                            Some(rest) => {
+                                base = format!("{}\n{}  *Rest", base, indent);
+                                base = format!("{}\n{}", base, rest.to_string_tree_helper(format!("    {}", indent)));
+                            }
+                            None => {}
+                        }
                         for b in l.body.borrow().iter() {
                             base = format!("{}\n{}", base, b.to_string_tree_helper(format!("  {}", indent)));
                         }
@@ -1353,6 +2001,13 @@ impl<'a> Ast<'a> {
                         for args in l.arguments.borrow().iter() {
                             base = format!("{}\n{}", base, args.to_string_tree_helper(format!("    {}", indent)));
                         }
+                        match l.rest.get() {
+                            Some(rest) => {
+                                base = format!("{}\n{}  *Rest", base, indent);
+                                base = format!("{}\n{}", base, rest.to_string_tree_helper(format!("    {}", indent)));
+                            }
+                            None => {}
+                        }
                         for b in l.body.borrow().iter() {
                             base = format!("{}\n{}", base, b.to_string_tree_helper(format!("  {}", indent)));
                         }
@@ -1415,17 +2070,33 @@ impl<'a> Ast<'a> {
                                     }
                                 }
                             }
+                            SymbolMode::RestParameter{index, depth} => {
+                                match depth {
+                                    SymbolDepth::Origin => {
+                                        format!("RestParameter(index = {}, origin)", index)
+                                    },
+                                    SymbolDepth::Depth(d) => {
+                                        format!("RestParameter(index = {}, depth = {})", index, d)
+                                    }
+                                }
+                            }
                             SymbolMode::Unresolved => "Unresolved".to_string(),
                         };
                         format!("{}Symbol[mode = {}]({}, {})", indent, mode, sym.token(), sym.value)
                     }
                     &Ast::Integer(ref integer) => format!("{}Integer({}, {})", indent, integer.token(), integer.value),
+                    &Ast::Long(ref l) => format!("{}Long({}, {})", indent, l.token(), l.value),
+                    &Ast::BigInteger(ref b) => format!("{}BigInteger({}, {})", indent, b.token(), b.value),
+                    &Ast::BigDecimal(ref b) => format!("{}BigDecimal({}, {})", indent, b.token(), b.value),
+                    &Ast::Ratio(ref r) => format!("{}Ratio({}, {}/{})", indent, r.token(), r.numerator, r.denominator),
                     &Ast::Double(ref d) => format!("{}Double({}, {})", indent, d.token(), d.value),
                     &Ast::String(ref s) => format!("{}String({}, '{}')", indent, s.token(), s.value),
+                    &Ast::UChar(ref c) => format!("{}UChar({}, {})", indent, c.token(), c.value),
                     &Ast::Keyword(ref k) => format!("{}Keyword({}, {})", indent, k.token(), k.value),
                     &Ast::Boolean(ref b) => format!("{}Boolean({}, {})", indent, b.token(), b.value),
                     &Ast::RegExp(ref r) => format!("{}RegExp({}, {})", indent, r.token(), r.value),
                     &Ast::Nil(ref n) => format!("{}Nil({})", indent, n.token()),
+                    &Ast::Error(ref e) => format!("{}Error({}, {})", indent, e.token(), e.message),
                     _ => {"".to_string()}
                 }
             }
@@ -61,6 +61,8 @@ impl<'a, 'b> AstVisitor<'a, bool> for RegisterDefinitionPass<'a, 'b> {
 
     fn visit_def(&self, ast: &'a Ast<'a>) -> bool {true}
 
+    fn visit_ns(&self, ast: &'a Ast<'a>) -> bool {true}
+
     fn visit_vector(&self, ast: &'a Ast<'a>) -> bool {true}
 
     fn visit_module_ref(&self, ast: &'a Ast<'a>) -> bool {true}
@@ -71,6 +73,14 @@ impl<'a, 'b> AstVisitor<'a, bool> for RegisterDefinitionPass<'a, 'b> {
 
     fn visit_integer(&self, ast: &'a Ast<'a>) -> bool {true}
 
+    fn visit_long(&self, ast: &'a Ast<'a>) -> bool {true}
+
+    fn visit_big_integer(&self, ast: &'a Ast<'a>) -> bool {true}
+
+    fn visit_big_decimal(&self, ast: &'a Ast<'a>) -> bool {true}
+
+    fn visit_ratio(&self, ast: &'a Ast<'a>) -> bool {true}
+
     fn visit_double(&self, ast: &'a Ast<'a>) -> bool {true}
 
     fn visit_string(&self, ast: &'a Ast<'a>) -> bool {true}
@@ -88,6 +98,8 @@ impl<'a, 'b> AstVisitor<'a, bool> for RegisterDefinitionPass<'a, 'b> {
     fn visit_uchar(&self, ast: &'a Ast<'a>) -> bool {true}
 
     fn visit_nil(&self, ast: &'a Ast<'a>) -> bool {true}
+
+    fn visit_error(&self, ast: &'a Ast<'a>) -> bool {true}
 }
 
 
@@ -135,7 +147,7 @@ mod test {
         c.dump();
         {
             let pass = RegisterDefinitionPass::new(&gc, &ir, &builtin_token_registry);
-            let parser = parser::Parser::new_from_file(&module_info, &lb, &zone_allocator);
+            let parser = parser::Parser::new_from_file(&module_info, &lb, &zone_allocator).ok().unwrap();
             let ret = parser.parse();
             match ret {
                 Ok(r) => {
@@ -5,23 +5,43 @@
 
 extern crate risp;
 
-use risp::parser::token;
+use std::path::{Path, PathBuf};
+use std::process;
 use risp::parser::literal_buffer;
-use risp::parser::moduleinfo;
-use risp::parser::parseerror;
-use risp::parser::parser;
+use risp::parser::loader::{self, ModuleResolver};
 use risp::internal::heap::zone::{ZoneAllocator};
 
+/// risp <file> [source-root...]
+/// The namespaces required by the file are searched in the source roots, the directory
+/// of the file if no root is given, and then in the directories of RISP_PATH.
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        eprintln!("Usage: {} <file> [source-root...]", args[0]);
+        process::exit(1);
+    }
+    let filename = &args[1];
+    let mut roots: Vec<PathBuf> = args[2..].iter().map(PathBuf::from).collect();
+    if roots.is_empty() {
+        roots.push(Path::new(filename).parent().unwrap_or(Path::new(".")).to_path_buf());
+    }
+
+    let resolver = ModuleResolver::from_env(roots);
+    let graph = match loader::load_module_graph(filename, &resolver) {
+        Ok(graph) => graph,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
     let zone_allocator = ZoneAllocator::new();
-    let module_info = moduleinfo::ModuleInfo::new("test/test_files/test.rp");
     let lb = literal_buffer::LiteralBuffer::new(&zone_allocator);
-    let parser = parser::Parser::new_from_file(&module_info, &lb, &zone_allocator);
-    {
-        let ret = parser.parse();
-        match ret {
-            Ok(r) => {println!("{}", r.to_string_tree());}
-            Err(e) => {println!("{}", e);}
+    match graph.parse(&lb, &zone_allocator) {
+        Ok(module) => {println!("{}", module.to_string_tree());}
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
         }
     }
 }
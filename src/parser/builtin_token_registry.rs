@@ -29,10 +29,32 @@
 use parser::literal_buffer::{LiteralBuffer};
 
 
+/// Forms which are handled by the parser instead of being called.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SpecialForm {
+    Quote,
+    Let,
+    Fn,
+    If,
+    Def,
+    DefMacro,
+    Defn,
+    /// `defn-`, which defines a private fn.
+    DefnPrivate,
+    Ns
+}
+
+
 pub struct BuiltinTokenRegistry {
     defn: i64,
+    defn_private: i64,
     def: i64,
-    defmacro: i64
+    defmacro: i64,
+    quote: i64,
+    let_: i64,
+    fn_: i64,
+    if_: i64,
+    ns: i64
 }
 
 
@@ -43,8 +65,14 @@ impl BuiltinTokenRegistry {
         let defmacro = literal_buffer.get("defmacro");
         BuiltinTokenRegistry {
             defn: defn,
+            defn_private: literal_buffer.get("defn-"),
             def: def,
-            defmacro: defmacro
+            defmacro: defmacro,
+            quote: literal_buffer.get("quote"),
+            let_: literal_buffer.get("let"),
+            fn_: literal_buffer.get("fn"),
+            if_: literal_buffer.get("if"),
+            ns: literal_buffer.get("ns")
         }
     }
 
@@ -52,11 +80,60 @@ impl BuiltinTokenRegistry {
         self.defn == v
     }
 
+    pub fn is_defn_private(&self, v: i64) -> bool {
+        self.defn_private == v
+    }
+
     pub fn is_def(&self, v: i64) -> bool {
-        self.defn == v
+        self.def == v
     }
 
     pub fn is_defmacro(&self, v: i64) -> bool {
-        self.defn == v
+        self.defmacro == v
+    }
+
+    /// The special form named by the symbol value `v`, if any.
+    pub fn special_form(&self, v: i64) -> Option<SpecialForm> {
+        if v == self.quote {
+            Some(SpecialForm::Quote)
+        } else if v == self.let_ {
+            Some(SpecialForm::Let)
+        } else if v == self.fn_ {
+            Some(SpecialForm::Fn)
+        } else if v == self.if_ {
+            Some(SpecialForm::If)
+        } else if v == self.def {
+            Some(SpecialForm::Def)
+        } else if v == self.defmacro {
+            Some(SpecialForm::DefMacro)
+        } else if v == self.defn {
+            Some(SpecialForm::Defn)
+        } else if v == self.defn_private {
+            Some(SpecialForm::DefnPrivate)
+        } else if v == self.ns {
+            Some(SpecialForm::Ns)
+        } else {
+            None
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use internal::heap::zone::ZoneAllocator;
+
+    #[test]
+    fn test_registry() {
+        let zone_allocator = ZoneAllocator::new();
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let registry = BuiltinTokenRegistry::new(&lb);
+        assert!(registry.is_def(lb.get("def")));
+        assert!(!registry.is_def(lb.get("defn")));
+        assert!(registry.is_defmacro(lb.get("defmacro")));
+        assert_eq!(registry.special_form(lb.get("fn")), Some(SpecialForm::Fn));
+        assert_eq!(registry.special_form(lb.get("defn-")), Some(SpecialForm::DefnPrivate));
+        assert_eq!(registry.special_form(lb.get("fn*")), None);
     }
 }
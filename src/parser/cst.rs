@@ -0,0 +1,287 @@
+///! Lossless concrete syntax tree.
+///!
+///! Unlike the Ast, the concrete syntax tree keeps every token together with
+///! the whitespaces and comments around it, so the source can be reprinted
+///! byte-for-byte. This is what the formatter and refactoring tools work on.
+///!
+///! Author Taketoshi Aono
+
+use std::result::{Result};
+use std::string::String;
+
+use parser::token::{Token, TokenKind};
+use parser::moduleinfo::ModuleInfo;
+use parser::scanner::Scanner;
+use parser::loader::LoadError;
+use parser::parseerror::ParseError;
+use parser::literal_buffer::LiteralBuffer;
+
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CstKind {
+    Module,
+    List,
+    Vector,
+    Map,
+    Set,
+    ShortLambda,
+    /// Reader macro prefix such as ' ` ~ ~@ @ ^ #_ #' #? #tag and the form(s) it applies to.
+    ReaderMacro,
+    Token
+}
+
+
+/// A piece of trivia.
+#[derive(Debug, PartialEq)]
+pub enum Trivia<'a> {
+    Whitespace(&'a str),
+    Comment(&'a str)
+}
+
+
+pub struct Cst<'a> {
+    kind: CstKind,
+    token: Option<Token<'a>>,
+    children: Vec<Cst<'a>>
+}
+
+
+pub type CstResult<'a> = Result<Cst<'a>, ParseError<'a>>;
+
+
+impl<'a> Cst<'a> {
+    fn new_node(kind: CstKind) -> Cst<'a> {
+        Cst {
+            kind: kind,
+            token: None,
+            children: Vec::new()
+        }
+    }
+
+
+    fn new_token(token: Token<'a>) -> Cst<'a> {
+        Cst {
+            kind: CstKind::Token,
+            token: Some(token),
+            children: Vec::new()
+        }
+    }
+
+
+    pub fn kind(&self) -> CstKind {
+        self.kind
+    }
+
+
+    /// The token of a leaf, None for an inner node.
+    pub fn token(&self) -> Option<Token<'a>> {
+        self.token
+    }
+
+
+    /// Children of an inner node, including the delimiter tokens.
+    pub fn children(&self) -> &Vec<Cst<'a>> {
+        &self.children
+    }
+
+
+    /// Reprint the tree exactly as it was read.
+    pub fn to_source(&self, literal_buffer: &LiteralBuffer<'a>) -> String {
+        let mut buf = String::new();
+        self.write_source(literal_buffer, &mut buf);
+        buf
+    }
+
+
+    fn write_source(&self, literal_buffer: &LiteralBuffer<'a>, buf: &mut String) {
+        match self.token {
+            Some(token) => {
+                for id in &[token.leading_trivia(), token.raw(), token.trailing_trivia()] {
+                    if *id >= 0 {
+                        buf.push_str(literal_buffer.find(*id));
+                    }
+                }
+            }
+            None => {
+                for child in self.children.iter() {
+                    child.write_source(literal_buffer, buf);
+                }
+            }
+        }
+    }
+}
+
+
+/// Split trivia text into whitespaces and comments.
+pub fn split_trivia<'a>(trivia: &'a str) -> Vec<Trivia<'a>> {
+    let mut ret = Vec::new();
+    let mut rest = trivia;
+    while !rest.is_empty() {
+        if rest.starts_with(';') {
+            let end = rest.find(|c| c == '\n' || c == '\r').unwrap_or(rest.len());
+            ret.push(Trivia::Comment(&rest[..end]));
+            rest = &rest[end..];
+        } else {
+            let end = rest.find(';').unwrap_or(rest.len());
+            ret.push(Trivia::Whitespace(&rest[..end]));
+            rest = &rest[end..];
+        }
+    }
+    ret
+}
+
+
+pub struct CstParser<'a> {
+    scanner: Scanner<'a>,
+    literal_buffer: &'a LiteralBuffer<'a>
+}
+
+
+impl<'a> CstParser<'a> {
+    pub fn new_from_file(module_info: &'a ModuleInfo, literal_buffer: &'a LiteralBuffer<'a>) -> Result<CstParser<'a>, LoadError> {
+        let scanner = match Scanner::new_file(module_info, literal_buffer) {
+            Ok(scanner) => scanner,
+            Err(e) => {return Err(e);}
+        };
+        scanner.set_preserve_trivia(true);
+        Ok(CstParser {
+            scanner: scanner,
+            literal_buffer: literal_buffer
+        })
+    }
+
+
+    pub fn new_from_code(module_info: &'a ModuleInfo, code: &str, literal_buffer: &'a LiteralBuffer<'a>) -> CstParser<'a> {
+        let scanner = Scanner::new_code(module_info, code, literal_buffer);
+        scanner.set_preserve_trivia(true);
+        CstParser {
+            scanner: scanner,
+            literal_buffer: literal_buffer
+        }
+    }
+
+
+    /// Parse the whole input. The last child of the module is the Eof token,
+    /// which holds the trivia at the end of the input.
+    pub fn parse(&self) -> CstResult<'a> {
+        let mut module = Cst::new_node(CstKind::Module);
+        loop {
+            let token = self.scanner.scan();
+            match token.kind() {
+                TokenKind::Eof => {
+                    module.children.push(Cst::new_token(token));
+                    return Ok(module);
+                }
+                _ => {
+                    match self.parse_form(token) {
+                        Ok(cst) => module.children.push(cst),
+                        Err(e) => {return Err(e);}
+                    }
+                }
+            }
+        }
+    }
+
+
+    fn parse_form(&self, token: Token<'a>) -> CstResult<'a> {
+        match token.kind() {
+            TokenKind::LeftParen => self.parse_seq(token, CstKind::List, TokenKind::RightParen),
+            TokenKind::ShortLambdaBegin => self.parse_seq(token, CstKind::ShortLambda, TokenKind::RightParen),
+            TokenKind::LeftBracket => self.parse_seq(token, CstKind::Vector, TokenKind::RightBracket),
+            TokenKind::LeftBrace => self.parse_seq(token, CstKind::Map, TokenKind::RightBrace),
+            TokenKind::SetBegin => self.parse_seq(token, CstKind::Set, TokenKind::RightBrace),
+            TokenKind::Discard |
+            TokenKind::VarQuote |
+            TokenKind::ReaderConditional |
+            TokenKind::ReaderConditionalSplicing |
+            TokenKind::TaggedLiteral |
+            TokenKind::QuoteRm |
+            TokenKind::Backtick |
+            TokenKind::Unquote |
+            TokenKind::UnquoteSplicing |
+            TokenKind::Deref => self.parse_reader_macro(token, 1),
+            TokenKind::Tag => self.parse_reader_macro(token, 2),
+            TokenKind::Invalid => {
+                if token.value() >= 0 {
+                    return Err(ParseError::new(self.literal_buffer.find(token.value()), token));
+                }
+                Err(ParseError::new("Invalid token.", token))
+            }
+            TokenKind::Eof => Err(ParseError::new("Unexpected end of input", token)),
+            TokenKind::RightParen |
+            TokenKind::RightBracket |
+            TokenKind::RightBrace => Err(ParseError::new("Unbalanced closing delimiter.", token)),
+            _ => Ok(Cst::new_token(token))
+        }
+    }
+
+
+    fn parse_seq(&self, token: Token<'a>, kind: CstKind, close: TokenKind) -> CstResult<'a> {
+        let mut node = Cst::new_node(kind);
+        node.children.push(Cst::new_token(token));
+        loop {
+            let next = self.scanner.scan();
+            if next.is(close) {
+                node.children.push(Cst::new_token(next));
+                return Ok(node);
+            }
+            match self.parse_form(next) {
+                Ok(cst) => node.children.push(cst),
+                Err(e) => {return Err(e);}
+            }
+        }
+    }
+
+
+    fn parse_reader_macro(&self, token: Token<'a>, arity: usize) -> CstResult<'a> {
+        let mut node = Cst::new_node(CstKind::ReaderMacro);
+        node.children.push(Cst::new_token(token));
+        for _ in 0..arity {
+            let next = self.scanner.scan();
+            match self.parse_form(next) {
+                Ok(cst) => node.children.push(cst),
+                Err(e) => {return Err(e);}
+            }
+        }
+        Ok(node)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use parser::literal_buffer::LiteralBuffer;
+    use parser::moduleinfo::ModuleInfo;
+    use parser::loader;
+    use internal::heap::zone::ZoneAllocator;
+
+    #[test]
+    fn test_round_trip() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test/test_files/parse_test.clj");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let code = loader::load(module_info.filename()).ok().unwrap();
+        let parser = CstParser::new_from_code(&module_info, &code, &lb);
+        let cst = parser.parse().ok().unwrap();
+        assert_eq!(cst.to_source(&lb), code);
+    }
+
+    #[test]
+    fn test_trivia() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let parser = CstParser::new_from_code(&module_info, ";; doc\n(a b) ; tail\n  ", &lb);
+        let cst = parser.parse().ok().unwrap();
+        let list = &cst.children()[0];
+        assert_eq!(list.kind(), CstKind::List);
+        let open = list.children()[0].token().unwrap();
+        assert_eq!(split_trivia(lb.find(open.leading_trivia())),
+                   vec![Trivia::Comment(";; doc"), Trivia::Whitespace("\n")]);
+        let close = list.children()[3].token().unwrap();
+        assert_eq!(lb.find(close.trailing_trivia()), " ; tail\n");
+        let eof = cst.children()[1].token().unwrap();
+        assert_eq!(lb.find(eof.leading_trivia()), "  ");
+    }
+}
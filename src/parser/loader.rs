@@ -3,17 +3,439 @@
 ///!
 
 use std;
-use std::io::Read;
+use std::io::{Read, BufReader};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::fmt::{Display, Formatter};
 use std::result::Result;
+use parser::moduleinfo::ModuleInfo;
+use parser::literal_buffer::LiteralBuffer;
+use parser::parser::Parser;
+use internal::heap::zone::ZoneAllocator;
+use internal::ast::Ast;
 
-pub fn load(src: &str) -> String {
-    let mut mf = std::fs::File::open(src);
-    match mf {
+pub fn load(src: &str) -> Result<String, LoadError> {
+    match std::fs::File::open(src) {
         Result::Ok(mut f) => {
             let mut s = String::new();
-            f.read_to_string(&mut s);
-            s
+            match f.read_to_string(&mut s) {
+                Result::Ok(_) => Result::Ok(s),
+                Result::Err(err) => Result::Err(LoadError::io(src, err))
+            }
         },
-        Result::Err(err) => panic!(err)
+        Result::Err(err) => Result::Err(LoadError::io(src, err))
+    }
+}
+
+
+/// Open the source file to be read incrementally.
+pub fn open(src: &str) -> Result<BufReader<std::fs::File>, LoadError> {
+    match std::fs::File::open(src) {
+        Result::Ok(f) => Result::Ok(BufReader::new(f)),
+        Result::Err(err) => Result::Err(LoadError::io(src, err))
+    }
+}
+
+
+/// Extension of the source files.
+const SOURCE_EXTENSION: &'static str = "rp";
+
+
+/// Environment variable of the source roots searched after the configured ones,
+/// separated like PATH.
+pub const RISP_PATH: &'static str = "RISP_PATH";
+
+
+/// Error while reading the modules.
+#[derive(Debug, PartialEq)]
+pub enum LoadError {
+    /// The source file could not be read.
+    Io {
+        path: String,
+        message: String
+    },
+    /// No source file of the required namespace in any source root.
+    NotFound {
+        namespace: String,
+        tried: Vec<PathBuf>
+    },
+    /// The parse errors of the module, one per line.
+    Parse {
+        filename: String,
+        message: String
+    },
+    /// The namespaces which require each other, the first one is repeated at the end.
+    Cycle(Vec<String>)
+}
+
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            &LoadError::Io {ref path, ref message} => {
+                write!(f, "Could not read {}: {}", path, message)
+            }
+            &LoadError::NotFound {ref namespace, ref tried} => {
+                let paths: Vec<String> = tried.iter().map(|p| format!("  {}", p.display())).collect();
+                write!(f, "Could not find the namespace {}, tried:\n{}", namespace, paths.join("\n"))
+            }
+            &LoadError::Parse {ref filename, ref message} => {
+                write!(f, "Could not parse {}.\n{}", filename, message)
+            }
+            &LoadError::Cycle(ref namespaces) => {
+                write!(f, "Cyclic dependency among the namespaces {}.", namespaces.join(" -> "))
+            }
+        }
+    }
+}
+
+
+impl LoadError {
+    fn io(path: &str, err: std::io::Error) -> LoadError {
+        LoadError::Io {
+            path: path.to_string(),
+            message: err.to_string()
+        }
+    }
+}
+
+
+/// Finds the source file of a namespace in the source roots, which are searched in order.
+pub struct ModuleResolver {
+    roots: Vec<PathBuf>
+}
+
+
+impl ModuleResolver {
+    pub fn new(roots: Vec<PathBuf>) -> ModuleResolver {
+        ModuleResolver {
+            roots: roots
+        }
+    }
+
+
+    /// The resolver searching `roots` and then the directories of RISP_PATH.
+    pub fn from_env(roots: Vec<PathBuf>) -> ModuleResolver {
+        ModuleResolver::with_search_path(roots, std::env::var_os(RISP_PATH).as_ref().map(|p| p.as_os_str()))
+    }
+
+
+    /// The resolver searching `roots` and then the directories of `search_path`,
+    /// which is separated like PATH.
+    pub fn with_search_path(roots: Vec<PathBuf>, search_path: Option<&OsStr>) -> ModuleResolver {
+        let mut roots = roots;
+        match search_path {
+            Some(paths) => roots.extend(std::env::split_paths(paths).filter(|p| !p.as_os_str().is_empty())),
+            None => {}
+        }
+        ModuleResolver::new(roots)
+    }
+
+
+    pub fn roots(&self) -> &Vec<PathBuf> {
+        &self.roots
+    }
+
+
+    /// The source file of `namespace` in the first source root which has it.
+    pub fn resolve(&self, namespace: &str) -> Result<PathBuf, LoadError> {
+        let mut tried = Vec::new();
+        for root in self.roots.iter() {
+            let path = module_path(root, namespace);
+            if path.is_file() {
+                return Result::Ok(path);
+            }
+            tried.push(path);
+        }
+        Result::Err(LoadError::NotFound {
+            namespace: namespace.to_string(),
+            tried: tried
+        })
+    }
+}
+
+
+/// The modules reachable from the root module by the requires of their ns forms,
+/// ordered so that every module comes after the modules it depends on.
+pub struct ModuleGraph {
+    modules: Vec<ModuleInfo>,
+    /// The namespace of each module, or its filename if it has no ns form.
+    namespaces: Vec<String>
+}
+
+
+impl ModuleGraph {
+    /// The modules in the dependency order, the root module is the last.
+    /// They are filled in by `parse`.
+    pub fn modules(&self) -> &Vec<ModuleInfo> {
+        &self.modules
+    }
+
+
+    pub fn root(&self) -> &ModuleInfo {
+        self.modules.last().unwrap()
+    }
+
+
+    pub fn find(&self, namespace: &str) -> Option<&ModuleInfo> {
+        self.namespaces.iter().position(|n| n == namespace).map(|i| &self.modules[i])
+    }
+
+
+    /// Parse the modules in the dependency order, each one with the modules loaded before it
+    /// registered to the parser, and return the Ast of the root module.
+    pub fn parse<'a>(&'a self, literal_buffer: &'a LiteralBuffer<'a>, zone_allocator: &'a ZoneAllocator) -> Result<&'a Ast<'a>, LoadError> {
+        let mut module = None;
+        for (i, module_info) in self.modules.iter().enumerate() {
+            let parser = match Parser::new_from_file(module_info, literal_buffer, zone_allocator) {
+                Ok(parser) => parser,
+                Err(e) => {return Err(e);}
+            };
+            for dependency in self.modules[..i].iter() {
+                parser.add_module(dependency);
+            }
+            match parser.parse() {
+                Ok(ast) => module = Some(ast),
+                Err(e) => {
+                    return Err(LoadError::Parse {
+                        filename: module_info.filename().to_string(),
+                        message: e.to_string()
+                    });
+                }
+            }
+        }
+        Ok(module.unwrap())
+    }
+}
+
+
+/// The source file of `namespace` under `source_root`,
+/// `my.app-core` is found at `my/app_core.rp`.
+pub fn module_path(source_root: &Path, namespace: &str) -> PathBuf {
+    let mut path = source_root.to_path_buf();
+    for segment in namespace.split('.') {
+        path.push(segment.replace('-', "_"));
+    }
+    path.set_extension(SOURCE_EXTENSION);
+    path
+}
+
+
+/// Find the module at `root` and the modules it requires transitively by reading their ns forms,
+/// the required modules are found by `resolver`. The modules are parsed by `ModuleGraph::parse`.
+pub fn load_module_graph(root: &str, resolver: &ModuleResolver) -> Result<ModuleGraph, LoadError> {
+    let header = match read_header(root) {
+        Ok(header) => header,
+        Err(e) => {return Err(e);}
+    };
+    let mut loader = GraphLoader {
+        resolver: resolver,
+        headers: Vec::new(),
+        loading: Vec::new()
+    };
+    match loader.visit(header) {
+        Ok(()) => {
+            Ok(ModuleGraph {
+                modules: loader.headers.iter().map(|h| ModuleInfo::new(h.filename())).collect(),
+                namespaces: loader.headers.iter().map(|h| namespace_of(h)).collect()
+            })
+        }
+        Err(e) => Err(e)
+    }
+}
+
+
+/// Read only the ns form of the module, the ModuleInfo has the namespace and the requires.
+fn read_header(filename: &str) -> Result<ModuleInfo, LoadError> {
+    let module_info = ModuleInfo::new(filename);
+    {
+        let zone_allocator = ZoneAllocator::new();
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let parser = match Parser::new_from_file(&module_info, &lb, &zone_allocator) {
+            Ok(parser) => parser,
+            Err(e) => {return Err(e);}
+        };
+        match parser.parse_header() {
+            Ok(()) => {}
+            Err(e) => {
+                return Err(LoadError::Parse {
+                    filename: filename.to_string(),
+                    message: e.to_string()
+                });
+            }
+        }
+    }
+    Ok(module_info)
+}
+
+
+fn namespace_of(module_info: &ModuleInfo) -> String {
+    match module_info.namespace() {
+        Some(namespace) => namespace,
+        None => module_info.filename().to_string()
+    }
+}
+
+
+struct GraphLoader<'a> {
+    resolver: &'a ModuleResolver,
+    /// The ns forms of the modules whose dependencies are loaded, in the dependency order.
+    headers: Vec<ModuleInfo>,
+    /// Namespaces of the modules whose dependencies are being loaded, the innermost last.
+    loading: Vec<String>
+}
+
+
+impl<'a> GraphLoader<'a> {
+    fn visit(&mut self, header: ModuleInfo) -> Result<(), LoadError> {
+        self.loading.push(namespace_of(&header));
+
+        for dependency in header.dependencies() {
+            match self.loading.iter().position(|n| *n == dependency) {
+                Some(start) => {
+                    let mut cycle = self.loading[start..].to_vec();
+                    cycle.push(dependency);
+                    return Err(LoadError::Cycle(cycle));
+                }
+                None => {}
+            }
+            if self.headers.iter().any(|h| h.namespace() == Some(dependency.clone())) {
+                continue;
+            }
+
+            let path = match self.resolver.resolve(&dependency) {
+                Ok(path) => path,
+                Err(e) => {return Err(e);}
+            };
+            let dependency_header = match read_header(&path.to_string_lossy()) {
+                Ok(header) => header,
+                Err(e) => {return Err(e);}
+            };
+            match self.visit(dependency_header) {
+                Err(e) => {return Err(e);}
+                _ => {}
+            }
+        }
+
+        self.loading.pop();
+        self.headers.push(header);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    fn write_sources(dir: &str, sources: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(dir);
+        let _ = fs::remove_dir_all(&root);
+        for &(namespace, code) in sources {
+            let path = module_path(&root, namespace);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::File::create(&path).unwrap().write_all(code.as_bytes()).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_module_path() {
+        let path = module_path(Path::new("src"), "my.app-core.util");
+        assert_eq!(path, Path::new("src/my/app_core/util.rp"));
+    }
+
+    #[test]
+    fn test_load_module_graph() {
+        let root = write_sources("risp_loader_graph", &[
+            ("app.main", "(ns app.main (:require [app.db :as db] [app.util :refer [f]]))"),
+            ("app.db", "(ns app.db (:require app.util))"),
+            ("app.util", "(ns app.util) (defn f [x] x)")
+        ]);
+        let main = module_path(&root, "app.main");
+        let graph = load_module_graph(&main.to_string_lossy(), &ModuleResolver::new(vec![root.clone()])).ok().unwrap();
+        let filenames: Vec<&str> = graph.modules().iter().map(|m| m.filename()).collect();
+        assert_eq!(filenames, vec![module_path(&root, "app.util").to_string_lossy(), module_path(&root, "app.db").to_string_lossy(),
+                                   main.to_string_lossy()]);
+        assert_eq!(graph.find("app.db").unwrap().filename(), module_path(&root, "app.db").to_string_lossy());
+
+        let zone_allocator = ZoneAllocator::new();
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let module = graph.parse(&lb, &zone_allocator).ok().unwrap();
+        assert_eq!(module.children().unwrap().len(), 1);
+        assert_eq!(graph.root().resolve_alias("db"), Some("app.db".to_string()));
+        assert_eq!(graph.find("app.db").unwrap().dependencies(), vec!["app.util"]);
+        assert!(graph.find("app.util").unwrap().defines_var("f"));
+    }
+
+    #[test]
+    fn test_parse_module_graph() {
+        let root = write_sources("risp_loader_parse", &[
+            ("app.main", "(ns app.main (:require [app.util :refer :all])) (f) (g)"),
+            ("app.private", "(ns app.private (:require [app.util :as u])) (u/secret)"),
+            ("app.util", "(ns app.util) (defn f [] 1) (defn- secret [] 2)")
+        ]);
+        let resolver = ModuleResolver::new(vec![root.clone()]);
+        let zone_allocator = ZoneAllocator::new();
+        let lb = LiteralBuffer::new(&zone_allocator);
+
+        let graph = load_module_graph(&module_path(&root, "app.main").to_string_lossy(), &resolver).ok().unwrap();
+        let module = graph.parse(&lb, &zone_allocator).ok().unwrap();
+        let heads: Vec<Option<(&str, &str)>> = module.children().unwrap()[1..].iter().map(|c| c.children().unwrap()[0].qualified_name()).collect();
+        assert_eq!(heads, vec![Some(("app.util", "f")), None]);
+
+        let graph = load_module_graph(&module_path(&root, "app.private").to_string_lossy(), &resolver).ok().unwrap();
+        match graph.parse(&lb, &zone_allocator) {
+            Err(LoadError::Parse {filename, message}) => {
+                assert_eq!(filename, module_path(&root, "app.private").to_string_lossy());
+                assert!(message.starts_with("var: app.util/secret is private."));
+            }
+            _ => panic!("parse error expected")
+        }
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let root = write_sources("risp_loader_errors", &[
+            ("a", "(ns a (:require b))"),
+            ("b", "(ns b (:require [c :as c]))"),
+            ("c", "(ns c (:require a))"),
+            ("d", "(ns d (:require missing.ns))")
+        ]);
+        let resolver = ModuleResolver::new(vec![root.join("lib"), root.clone()]);
+        match load_module_graph(&module_path(&root, "a").to_string_lossy(), &resolver) {
+            Err(LoadError::Cycle(cycle)) => assert_eq!(cycle, vec!["a", "b", "c", "a"]),
+            _ => panic!("cycle expected")
+        }
+        match load_module_graph(&module_path(&root, "d").to_string_lossy(), &resolver) {
+            Err(LoadError::NotFound {namespace, tried}) => {
+                assert_eq!(namespace, "missing.ns");
+                assert_eq!(tried, vec![root.join("lib/missing/ns.rp"), root.join("missing/ns.rp")]);
+            }
+            _ => panic!("not found error expected")
+        }
+        match load(&root.join("none.rp").to_string_lossy()) {
+            Err(LoadError::Io {path, ..}) => assert!(path.ends_with("none.rp")),
+            _ => panic!("io error expected")
+        }
+    }
+
+    #[test]
+    fn test_resolver() {
+        let root = write_sources("risp_loader_resolver", &[
+            ("lib.a", "(ns lib.a)"),
+            ("env.b", "(ns env.b)")
+        ]);
+        let first = root.join("first");
+        let search_path = std::env::join_paths(vec![root.join("none"), root.clone()]).unwrap();
+        let resolver = ModuleResolver::with_search_path(vec![first.clone()], Some(&search_path));
+        assert_eq!(resolver.roots(), &vec![first.clone(), root.join("none"), root.clone()]);
+        assert_eq!(resolver.resolve("env.b").ok().unwrap(), root.join("env/b.rp"));
+        let err = resolver.resolve("lib.c").err().unwrap();
+        assert_eq!(err.to_string(), format!("Could not find the namespace lib.c, tried:\n  {}\n  {}\n  {}",
+                                            first.join("lib/c.rp").display(), root.join("none/lib/c.rp").display(),
+                                            root.join("lib/c.rp").display()));
     }
 }
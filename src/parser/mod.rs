@@ -11,3 +11,4 @@ pub mod sourceinfo;
 pub mod moduleinfo;
 pub mod parseerror;
 pub mod builtin_token_registry;
+pub mod cst;
@@ -3,10 +3,99 @@
 ///! Author Taketoshi Aono
 
 use std;
+use std::cell::{Ref, RefCell};
 use std::fmt::{Display, Formatter};
 
+
+/// Vars of the required module which are referred without the namespace.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Refer {
+    Nothing,
+    /// `:refer :all`
+    All,
+    /// `:refer [a b]`
+    Names(Vec<String>)
+}
+
+
+/// `[my.app.core :as core :refer [f]]` of `(:require ...)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Require {
+    module: String,
+    alias: Option<String>,
+    refer: Refer
+}
+
+
+impl Require {
+    pub fn new(module: &str, alias: Option<&str>, refer: Refer) -> Require {
+        Require {
+            module: module.to_string(),
+            alias: alias.map(|a| a.to_string()),
+            refer: refer
+        }
+    }
+
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    pub fn alias(&self) -> Option<&str> {
+        self.alias.as_ref().map(|a| &a[..])
+    }
+
+    pub fn refer(&self) -> &Refer {
+        &self.refer
+    }
+
+    /// Whether `name` is referred without the namespace.
+    pub fn refers(&self, name: &str) -> bool {
+        match self.refer {
+            Refer::Nothing => false,
+            Refer::All => true,
+            Refer::Names(ref names) => names.iter().any(|n| n == name)
+        }
+    }
+}
+
+
+/// `[java.util Timer TimerTask]` of `(:import ...)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Import {
+    package: String,
+    classes: Vec<String>
+}
+
+
+impl Import {
+    pub fn new(package: &str, classes: Vec<String>) -> Import {
+        Import {
+            package: package.to_string(),
+            classes: classes
+        }
+    }
+
+    pub fn package(&self) -> &str {
+        &self.package
+    }
+
+    pub fn classes(&self) -> &Vec<String> {
+        &self.classes
+    }
+}
+
+
+/// The file of the module and its namespace declared by the ns form,
+/// the namespace is filled in by the parser.
 pub struct ModuleInfo {
-    filename: String
+    filename: String,
+    namespace: RefCell<Option<String>>,
+    requires: RefCell<Vec<Require>>,
+    imports: RefCell<Vec<Import>>,
+    /// Vars defined by def, defn or defmacro.
+    vars: RefCell<Vec<String>>,
+    /// Vars defined by defn- or with `:private` metadata.
+    private_vars: RefCell<Vec<String>>
 }
 
 
@@ -20,11 +109,103 @@ impl Display for ModuleInfo {
 impl ModuleInfo {
     pub fn new(filename: &str) -> ModuleInfo {
         ModuleInfo {
-            filename: filename.to_string()
+            filename: filename.to_string(),
+            namespace: RefCell::new(None),
+            requires: RefCell::new(Vec::new()),
+            imports: RefCell::new(Vec::new()),
+            vars: RefCell::new(Vec::new()),
+            private_vars: RefCell::new(Vec::new())
         }
     }
 
     pub fn filename(&self) -> &str {
         &self.filename
     }
+
+    pub fn set_namespace(&self, namespace: &str) {
+        *self.namespace.borrow_mut() = Some(namespace.to_string());
+    }
+
+    /// The namespace declared by the ns form, None if the module has no ns form.
+    pub fn namespace(&self) -> Option<String> {
+        self.namespace.borrow().clone()
+    }
+
+    pub fn add_require(&self, require: Require) {
+        self.requires.borrow_mut().push(require);
+    }
+
+    pub fn requires(&self) -> Ref<Vec<Require>> {
+        self.requires.borrow()
+    }
+
+    pub fn add_import(&self, import: Import) {
+        self.imports.borrow_mut().push(import);
+    }
+
+    pub fn imports(&self) -> Ref<Vec<Import>> {
+        self.imports.borrow()
+    }
+
+    pub fn add_var(&self, name: &str) {
+        self.vars.borrow_mut().push(name.to_string());
+    }
+
+    /// Whether the var `name` is defined in this module.
+    pub fn defines_var(&self, name: &str) -> bool {
+        self.vars.borrow().iter().any(|v| v == name)
+    }
+
+    pub fn add_private_var(&self, name: &str) {
+        self.private_vars.borrow_mut().push(name.to_string());
+    }
+
+    /// Whether the var `name` of this module cannot be referred from the other namespaces.
+    pub fn is_private_var(&self, name: &str) -> bool {
+        self.private_vars.borrow().iter().any(|v| v == name)
+    }
+
+    /// Whether the module `namespace` is required.
+    pub fn requires_namespace(&self, namespace: &str) -> bool {
+        self.requires.borrow().iter().any(|r| r.module() == namespace)
+    }
+
+    /// The namespaces of the required modules in the order of the requires.
+    pub fn dependencies(&self) -> Vec<String> {
+        let mut dependencies: Vec<String> = Vec::new();
+        for require in self.requires.borrow().iter() {
+            if !dependencies.iter().any(|d| d == require.module()) {
+                dependencies.push(require.module().to_string());
+            }
+        }
+        dependencies
+    }
+
+    /// The namespace which `alias` of `(:require [x :as alias])` stands for.
+    pub fn resolve_alias(&self, alias: &str) -> Option<String> {
+        self.requires.borrow().iter()
+            .find(|r| r.alias() == Some(alias))
+            .map(|r| r.module().to_string())
+    }
+
+    /// The namespace which refers `name` by `:refer`. `:refer :all` refers only the public vars
+    /// of the module found in `modules`, the modules loaded before this one.
+    pub fn referred_namespace(&self, name: &str, modules: &[&ModuleInfo]) -> Option<String> {
+        self.requires.borrow().iter()
+            .find(|r| match r.refer() {
+                &Refer::All => {
+                    modules.iter().any(|m| m.namespace().as_ref().map(|n| &n[..]) == Some(r.module()) &&
+                                       m.defines_var(name) && !m.is_private_var(name))
+                }
+                _ => r.refers(name)
+            })
+            .map(|r| r.module().to_string())
+    }
+
+    /// The fully qualified name of the imported class `class`.
+    pub fn resolve_class(&self, class: &str) -> Option<String> {
+        self.imports.borrow().iter()
+            .find(|i| i.classes().iter().any(|c| c == class))
+            .map(|i| format!("{}.{}", i.package(), class))
+    }
 }
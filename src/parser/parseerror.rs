@@ -3,11 +3,13 @@
 ///! Author Taketoshi Aono
 
 use parser::token::Token;
+use internal::ast::Ast;
 use std::fmt::{Display, Result, Formatter};
 
 pub struct ParseError<'a> {
     message: String,
-    token: Token<'a>
+    token: Token<'a>,
+    incomplete: bool
 }
 
 impl<'a> Display for ParseError<'a> {
@@ -20,7 +22,82 @@ impl<'a> ParseError<'a> {
     pub fn new(message: &str, token: Token<'a>) -> ParseError<'a> {
         ParseError {
             message: message.to_string(),
-            token: token
+            token: token,
+            incomplete: false
         }
     }
+
+
+    /// Error caused by the end of input in the middle of a form,
+    /// the REPL reads more lines and tries again.
+    pub fn new_incomplete(message: &str, token: Token<'a>) -> ParseError<'a> {
+        ParseError {
+            message: message.to_string(),
+            token: token,
+            incomplete: true
+        }
+    }
+
+
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+
+    pub fn token(&self) -> Token<'a> {
+        self.token
+    }
+}
+
+
+/// All errors found in a module, with the partial module in which
+/// the forms that could not be parsed are replaced with Ast::Error.
+pub struct ParseErrors<'a> {
+    module: &'a Ast<'a>,
+    errors: Vec<ParseError<'a>>
+}
+
+impl<'a> Display for ParseErrors<'a> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let messages: Vec<String> = self.errors.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", messages.join("\n"))
+    }
+}
+
+impl<'a> ParseErrors<'a> {
+    pub fn new(module: &'a Ast<'a>, errors: Vec<ParseError<'a>>) -> ParseErrors<'a> {
+        ParseErrors {
+            module: module,
+            errors: errors
+        }
+    }
+
+
+    /// The partial module.
+    pub fn module(&self) -> &'a Ast<'a> {
+        self.module
+    }
+
+
+    /// The errors in the order of appearance.
+    pub fn errors(&self) -> &Vec<ParseError<'a>> {
+        &self.errors
+    }
+
+
+    pub fn into_errors(self) -> Vec<ParseError<'a>> {
+        self.errors
+    }
+
+
+    /// Whether all the errors are caused by the end of input,
+    /// the REPL reads more lines only if the input has no other mistakes.
+    pub fn is_incomplete(&self) -> bool {
+        self.errors.iter().all(|e| e.is_incomplete())
+    }
 }
@@ -2,27 +2,35 @@
 ///! Author Taketoshi Aono
 ///!
 
-use std::cell::{Cell};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::result::{Result};
+use std::io::BufRead;
 
 use parser::token::{Token, TokenKind};
-use parser::moduleinfo::ModuleInfo;
+use parser::moduleinfo::{ModuleInfo, Require, Refer, Import};
 use parser::scanner::Scanner;
-use parser::parseerror::ParseError;
+use parser::loader::LoadError;
+use parser::parseerror::{ParseError, ParseErrors};
 use parser::sourceinfo::SourceInfo;
 use parser::literal_buffer::LiteralBuffer;
+use parser::builtin_token_registry::{BuiltinTokenRegistry, SpecialForm};
 use internal::ast::*;
 use internal::heap::zone::{ZoneAllocator};
+use std;
 use std::rc::Rc;
+use regex;
+use regex::Regex;
+use std::{i32};
 
 macro_rules! check_token {
-    ($ast:ident, $token:expr, $rest:block) => {
+    ($s:expr, $ast:ident, $token:expr, $rest:block) => {
         match $token.kind() {
             TokenKind::Eof => {
-                return Result::Ok($ast);
+                return Result::Err(ParseError::new_incomplete("Unexpected end of input", $token));
             },
             TokenKind::Invalid => {
-                return Result::Err(ParseError::new("Invlaid token.", $token));
+                return Result::Err($s.invalid_token($token));
             }
             _ => $rest
         }
@@ -30,6 +38,16 @@ macro_rules! check_token {
 }
 
 
+macro_rules! next_token {
+    ($s:expr) => {
+        match $s.next_token() {
+            Ok(token) => token,
+            Err(e) => {return Result::Err(e);}
+        }
+    }
+}
+
+
 macro_rules! get_token_value {
     ($s:expr, $token:expr) => {$s.literal_buffer.find($token.value())}
 }
@@ -37,6 +55,7 @@ macro_rules! get_token_value {
 
 type Node<'a> = Ast<'a>;
 pub type ParseResult<'a> = Result<&'a Node<'a>, ParseError<'a>>;
+pub type ModuleResult<'a> = Result<&'a Node<'a>, ParseErrors<'a>>;
 
 
 pub struct ScopeHandler<'a> {
@@ -80,112 +99,319 @@ pub struct Parser<'a> {
     literal_buffer: &'a LiteralBuffer<'a>,
     scanner: Scanner<'a>,
     scope_handler: ScopeHandler<'a>,
+    builtin_token_registry: BuiltinTokenRegistry,
+    /// Namespace which the symbols in syntax-quote are qualified with.
+    namespace: Cell<&'a str>,
+    gensym_id: Cell<u32>,
+    /// Auto-gensyms of the enclosing syntax-quotes, `x#` to the generated name.
+    gensyms: RefCell<Vec<HashMap<i64, &'a str>>>,
+    tag_readers: RefCell<HashMap<std::string::String, TagReader<'a>>>,
+    /// Parameters of the #(...) being read, #(...) cannot be nested.
+    short_lambda: RefCell<Option<ShortLambda<'a>>>,
+    /// Opening delimiters of the forms being read, innermost last.
+    delimiters: RefCell<Vec<TokenKind>>,
+    last_token: Cell<Option<Token<'a>>>,
+    errors: RefCell<Vec<ParseError<'a>>>,
+    /// Modules already loaded, which the qualified symbols may refer to.
+    modules: RefCell<Vec<&'a ModuleInfo>>,
     zone_allocator: &'a ZoneAllocator
 }
 
 
+/// Generated parameters of #(...), %n is bound to `params[n - 1]` and %& to `rest`.
+struct ShortLambda<'a> {
+    id: u32,
+    token: Token<'a>,
+    scope: &'a Scope<'a>,
+    params: Vec<&'a Ast<'a>>,
+    rest: Option<&'a Ast<'a>>,
+    /// References of %&, their index is known after the whole body is read.
+    rest_refs: Vec<&'a Ast<'a>>
+}
+
+
+/// Binding form of let, fn or defmacro, the destructuring is desugared
+/// into the let bindings of the symbols in the form.
+enum BindingForm<'a> {
+    Symbol(&'a Ast<'a>),
+    /// `[a b & r :as all]`
+    Sequential {
+        token: Token<'a>,
+        elements: Vec<BindingForm<'a>>,
+        rest: Option<Box<BindingForm<'a>>>,
+        whole: Option<&'a Ast<'a>>,
+        meta: Option<&'a Ast<'a>>
+    },
+    /// `{a :a :keys [b c] :or {b 1} :as m}`, entries are the binding forms and their keys.
+    Associative {
+        token: Token<'a>,
+        entries: Vec<(BindingForm<'a>, &'a Ast<'a>)>,
+        defaults: Vec<(&'a Ast<'a>, &'a Ast<'a>)>,
+        whole: Option<&'a Ast<'a>>,
+        meta: Option<&'a Ast<'a>>
+    }
+}
+
+
+/// Destructured parameters of fn or defmacro and the generated parameters bound to them.
+type Destructuring<'a> = Vec<(BindingForm<'a>, &'a Ast<'a>)>;
+
+
+/// Reader function of a tagged literal, see `Parser::register_tag_reader`.
+pub type TagReader<'a> = Rc<Fn(&Parser<'a>, &'a Ast<'a>) -> Result<&'a Ast<'a>, std::string::String> + 'a>;
+
+
+/// Namespace of the functions which syntax-quote expands to.
+const CORE_NAMESPACE: &'static str = "risp.core";
+
+
+/// Namespace of the module without ns form.
+const DEFAULT_NAMESPACE: &'static str = "user";
+
+
+/// RFC3339 timestamp of `#inst`, compiled once by `Parser::new`.
+const TIMESTAMP_PATTERN: &'static str = r"^(\d{4})(?:-(\d\d)(?:-(\d\d)(?:T(\d\d)(?::(\d\d)(?::(\d\d)(?:\.\d+)?)?)?)?)?)?(?:Z|[-+](\d\d):(\d\d))?$";
+
+
+/// UUID of `#uuid`, compiled once by `Parser::new`.
+const UUID_PATTERN: &'static str = r"^[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$";
+
+
+/// Classes of `java.lang` which every namespace imports implicitly.
+const JAVA_LANG_CLASSES: &'static [&'static str] = &[
+    "Boolean", "Byte", "Character", "Class", "Double", "Error", "Exception", "Float", "IllegalArgumentException",
+    "IllegalStateException", "Integer", "Long", "Math", "Number", "Object", "Runtime", "RuntimeException", "Short",
+    "String", "StringBuilder", "System", "Thread", "Throwable"];
+
+
 impl<'a> Parser<'a> {
-    pub fn new_from_file(module_info: &'a ModuleInfo, literal_buffer: &'a LiteralBuffer<'a>, zone_allocator: &'a ZoneAllocator) -> Parser<'a> {
-        Parser {
-            module_info: module_info,
-            scanner: Scanner::new_file(module_info, literal_buffer),
-            literal_buffer: literal_buffer,
-            scope_handler: ScopeHandler::new(zone_allocator),
-            zone_allocator: zone_allocator
+    pub fn new_from_file(module_info: &'a ModuleInfo, literal_buffer: &'a LiteralBuffer<'a>, zone_allocator: &'a ZoneAllocator) -> Result<Parser<'a>, LoadError> {
+        match Scanner::new_file(module_info, literal_buffer) {
+            Ok(scanner) => Ok(Parser::new(module_info, scanner, literal_buffer, zone_allocator)),
+            Err(e) => Err(e)
         }
     }
 
 
     pub fn new_from_code(module_info: &'a ModuleInfo, code: &str, literal_buffer: &'a LiteralBuffer<'a>, zone_allocator: &'a ZoneAllocator) -> Parser<'a> {
-        Parser {
+        Parser::new(module_info, Scanner::new_code(module_info, code, literal_buffer), literal_buffer, zone_allocator)
+    }
+
+
+    /// Parse the input pulled from `reader`, e.g. stdin or a socket.
+    /// A ParseError which `is_incomplete` tells that the input ended in the middle of a form.
+    pub fn new_from_reader(module_info: &'a ModuleInfo, reader: Box<BufRead + 'a>, literal_buffer: &'a LiteralBuffer<'a>, zone_allocator: &'a ZoneAllocator) -> Parser<'a> {
+        Parser::new(module_info, Scanner::new_reader(module_info, reader, literal_buffer), literal_buffer, zone_allocator)
+    }
+
+
+    fn new(module_info: &'a ModuleInfo, scanner: Scanner<'a>, literal_buffer: &'a LiteralBuffer<'a>, zone_allocator: &'a ZoneAllocator) -> Parser<'a> {
+        let parser = Parser {
             module_info: module_info,
-            scanner: Scanner::new_code(module_info, code, literal_buffer),
+            scanner: scanner,
             literal_buffer: literal_buffer,
             scope_handler: ScopeHandler::new(zone_allocator),
+            builtin_token_registry: BuiltinTokenRegistry::new(literal_buffer),
+            namespace: Cell::new(DEFAULT_NAMESPACE),
+            gensym_id: Cell::new(0),
+            gensyms: RefCell::new(Vec::new()),
+            tag_readers: RefCell::new(HashMap::new()),
+            short_lambda: RefCell::new(None),
+            delimiters: RefCell::new(Vec::new()),
+            last_token: Cell::new(None),
+            errors: RefCell::new(Vec::new()),
+            modules: RefCell::new(Vec::new()),
             zone_allocator: zone_allocator
+        };
+        let timestamp = Regex::new(TIMESTAMP_PATTERN).unwrap();
+        parser.register_tag_reader("inst", move |parser: &Parser<'a>, form: &'a Ast<'a>| parser.read_inst(&timestamp, form));
+        let uuid = Regex::new(UUID_PATTERN).unwrap();
+        parser.register_tag_reader("uuid", move |parser: &Parser<'a>, form: &'a Ast<'a>| parser.read_uuid(&uuid, form));
+        parser
+    }
+
+
+    /// Register the reader function of the tagged literal `#tag form`.
+    /// The reader receives the parsed form following the tag and returns the form
+    /// which replaces the tagged literal, or the error message.
+    /// The reader registered later for the same tag wins, so the builtin #inst and #uuid can be replaced.
+    pub fn register_tag_reader<F>(&self, tag: &str, reader: F)
+        where F: Fn(&Parser<'a>, &'a Ast<'a>) -> Result<&'a Ast<'a>, std::string::String> + 'a {
+        self.tag_readers.borrow_mut().insert(tag.to_string(), Rc::new(reader));
+    }
+
+
+    /// Register the module loaded before this one, the private vars of the module
+    /// cannot be referred by the qualified symbols.
+    pub fn add_module(&self, module_info: &'a ModuleInfo) {
+        self.modules.borrow_mut().push(module_info);
+    }
+
+
+    /// The allocator of the Ast nodes, for the tag readers creating nodes.
+    pub fn zone_allocator(&self) -> &'a ZoneAllocator {
+        self.zone_allocator
+    }
+
+
+    pub fn literal_buffer(&self) -> &'a LiteralBuffer<'a> {
+        self.literal_buffer
+    }
+
+
+    /// Read exactly one form from `code`, see `parse_one`.
+    pub fn read_string(module_info: &'a ModuleInfo, code: &str, literal_buffer: &'a LiteralBuffer<'a>, zone_allocator: &'a ZoneAllocator) -> ParseResult<'a> {
+        Parser::new_from_code(module_info, code, literal_buffer, zone_allocator).parse_one()
+    }
+
+
+    /// Read exactly one form, such as a line of the REPL or a data file.
+    /// The input left after the form, other than whitespaces and comments, is an error.
+    pub fn parse_one(&self) -> ParseResult<'a> {
+        let token = next_token!(self);
+        let form = match self.parse_single_form(token) {
+            Ok(ast) => ast,
+            Err(e) => {return Result::Err(e);}
+        };
+        let next = next_token!(self);
+        if !next.is(TokenKind::Eof) {
+            return Result::Err(ParseError::new("Unexpected input after the form.", next));
+        }
+        Result::Ok(form)
+    }
+
+
+    /// Read only the first form of the module, which fills the namespace and the requires
+    /// of the ModuleInfo if it is the ns form. The rest of the module is not read.
+    pub fn parse_header(&self) -> Result<(), ParseError<'a>> {
+        let token = next_token!(self);
+        if token.is(TokenKind::Eof) {
+            return Result::Ok(());
+        }
+        match self.parse_single_form(token) {
+            Ok(_) => Result::Ok(()),
+            Err(e) => Result::Err(e)
         }
     }
 
 
-    pub fn parse(&self) -> ParseResult<'a> {
-        let token = Token::new(SourceInfo::new(1, 1, self.module_info), TokenKind::Root);
+    /// Parse the whole module.
+    /// The parser does not stop at the first error, the broken form is skipped until
+    /// its delimiters are balanced and replaced with Ast::Error, so all the errors are
+    /// reported at once along with the partial module.
+    pub fn parse(&self) -> ModuleResult<'a> {
         let module = Ast::new_module(self.zone_allocator, self.module_info, self.scope_handler.scope());
         loop {
-            let token = self.scanner.scan();
-            check_token!(module, token, {
-                match token.kind() {
-                    TokenKind::LeftParen => {
-                        match self.parse_form(token) {
-                            Ok(ast) => module.add_child(ast),
-                            Err(e) => {return Result::Err(e);}
-                        }
-                    }
-                    _ => {
-                        return Result::Err(ParseError::new("Invlaid token.", token));
-                    }
+            let token = match self.next_token() {
+                Ok(token) => token,
+                Err(e) => {
+                    let token = e.token();
+                    module.add_child(self.recover(0, token, e));
+                    continue;
                 }
-            })
+            };
+            let result = match token.kind() {
+                TokenKind::Eof => {
+                    break;
+                }
+                TokenKind::RightParen |
+                TokenKind::RightBracket |
+                TokenKind::RightBrace => Result::Err(ParseError::new("Unmatched delimiter.", token)),
+                _ => self.do_parse_form(module, token, |ast: &'a Ast<'a>| module.add_child(ast))
+            };
+            match result {
+                Ok(_) => {},
+                Err(e) => module.add_child(self.recover(0, token, e))
+            }
+        }
+
+        let errors = std::mem::replace(&mut *self.errors.borrow_mut(), Vec::new());
+        if errors.is_empty() {
+            Result::Ok(module)
+        } else {
+            Result::Err(ParseErrors::new(module, errors))
         }
     }
     
 
     fn parse_form(&self, token: Token<'a>) -> ParseResult<'a> {
-        let mut next = self.scanner.scan();
+        let depth = self.delimiters.borrow().len();
+        let mut next = next_token!(self);
         let form;
 
         match next.kind() {
             TokenKind::Eof => {
-                return Err(ParseError::new("Unexpected end of input", next));
+                return Err(ParseError::new_incomplete("Unexpected end of input", next));
             }
             TokenKind::Invalid => {
-                return Err(ParseError::new("Invalid token.", next));
-            }
-            TokenKind::Def => {
-                return self.parse_def(token);
-            },
-            TokenKind::DefMacro => {
-                return self.parse_defmacro();
-            }
-            TokenKind::Quote => {
-                return self.parse_quote(Ast::new_quote(self.zone_allocator, token));
+                return Err(self.invalid_token(next));
             }
-            TokenKind::Let => {
-                return self.parse_let(token);
-            }
-            TokenKind::Lambda => {
-                return self.parse_lambda(token);
-            },
-            TokenKind::If => {
-                return self.parse_if(Ast::new_if(self.zone_allocator, token));
+            TokenKind::Symbol => {
+                match self.special_form(next) {
+                    Some(SpecialForm::Def) => {
+                        return self.parse_def(token);
+                    }
+                    Some(SpecialForm::DefMacro) => {
+                        return self.parse_defmacro(token);
+                    }
+                    Some(SpecialForm::Defn) => {
+                        return self.parse_defn(token, false);
+                    }
+                    Some(SpecialForm::DefnPrivate) => {
+                        return self.parse_defn(token, true);
+                    }
+                    Some(SpecialForm::Ns) => {
+                        return self.parse_ns(token);
+                    }
+                    Some(SpecialForm::Quote) => {
+                        return self.parse_quote(token, next);
+                    }
+                    Some(SpecialForm::Let) => {
+                        return self.parse_let(token);
+                    }
+                    Some(SpecialForm::Fn) => {
+                        return self.parse_lambda(token);
+                    }
+                    Some(SpecialForm::If) => {
+                        return self.parse_if(Ast::new_if(self.zone_allocator, token));
+                    }
+                    None => {}
+                }
             }
+            _ => {}
+        }
+
+        match next.kind() {
             TokenKind::RightParen => {
-                return Result::Ok(Ast::new_list(self.zone_allocator, token));
+                return self.close(Ast::new_list(self.zone_allocator, token), next);
             }
             _ => {
                 form = Ast::new_list(self.zone_allocator, token);
-                match self.do_parse_form(form, next, |ast: &'a Ast<'a>| form.add_child(ast)) {
-                    Ok(ast) => {},
+                match self.parse_element(form, depth, next) {
+                    Ok(false) => {},
+                    Ok(true) => {return Result::Ok(form);}
                     Err(e) => {return Result::Err(e);}
                 }
             }
         }
-        next = self.scanner.scan();
+        next = next_token!(self);
 
         loop {
-            check_token!(form, next, {
+            check_token!(self, form, next, {
                 match next.kind() {
                     TokenKind::RightParen => {
-                        return Result::Ok(form);
+                        return self.close(form, next);
                     }
                     _ => {
-                        match self.do_parse_form(form, next, |ast: &'a Ast<'a>| form.add_child(ast)) {
-                            Ok(ast) => {},
+                        match self.parse_element(form, depth, next) {
+                            Ok(false) => {},
+                            Ok(true) => {return Result::Ok(form);}
                             Err(e) => {return Result::Err(e);}
                         }
                     }
                 }
             });
-            next = self.scanner.scan();
+            next = next_token!(self);
         }
     }
 
@@ -193,536 +419,3383 @@ impl<'a> Parser<'a> {
     fn parse_let(&self, token: Token<'a>) -> ParseResult<'a> {
         return self.scope_handler.enter(|scope: &'a Scope<'a>| -> ParseResult<'a> {
             let let_form = Ast::new_let(self.zone_allocator, token, scope);
-            let mut next_token = self.scanner.scan();
-            let mut binding: &'a Ast<'a>;
-            
-            if !next_token.is(TokenKind::LeftBracket) {
-                return Err(ParseError::new("let expect vector binding form.", next_token));
-            }
+            let mut next_token = next_token!(self);
+
+            check_token!(self, let_form, next_token, {
+                if !next_token.is(TokenKind::LeftBracket) {
+                    return Err(ParseError::new("let expect vector binding form.", next_token));
+                }
+            });
 
-            next_token = self.scanner.scan();
+            next_token = next_token!(self);
 
             loop {
-                match self.parse_literal(next_token) {
-                    Ok(ast) => {
-                        match ast {
-                            &Ast::Symbol(ref s) => {
-                                match ast.symbol_mode() {
-                                    SymbolMode::Unresolved => {
-                                        ast.set_symbol_mode(SymbolMode::Var(SymbolDepth::Origin));
-                                    }
-                                    _ => {}
-                                }
-                                binding = ast;
-                                scope.intern(binding);
-                            }
-                            _ => {return Err(ParseError::new("Invalid binding form", next_token));}
-                        }
-                    }
+                let form = match self.read_binding_form(next_token) {
+                    Ok(form) => form,
                     Err(e) => {return Err(e);}
-                }
+                };
 
-                next_token = self.scanner.scan();
-                match self.do_parse_form(let_form, next_token, |ast: &'a Ast<'a>| {
-                    binding.bind_to_symbol(ast);
-                    let_form.add_let_binding((binding, ast));
-                }) {
-                    Err(e) => {return Err(ParseError::new("let form expected symbol-value pair.", next_token));}
-                    _ => {}
+                // The symbol is bound after its value is read, so `x` of `(let [x x] x)`
+                // refers to the outer x.
+                next_token = next_token!(self);
+                match self.parse_single_form(next_token) {
+                    Ok(value) => self.bind_form(let_form, scope, &form, value),
+                    Err(e) => {
+                        if e.is_incomplete() {
+                            return Err(e);
+                        }
+                        return Err(ParseError::new("let form expected symbol-value pair.", next_token));
+                    }
                 }
 
-                next_token = self.scanner.scan();
+                next_token = next_token!(self);
                 if next_token.is(TokenKind::RightBracket) {
                     break;
                 }
             }
 
-            next_token = self.scanner.scan();
+            next_token = next_token!(self);
             loop {
                 match self.do_parse_form(let_form, next_token, |ast: &'a Ast<'a>| let_form.add_let_body(ast)) {
                     Err(e) => {return Err(e);},
                     _ => {}
                 }
 
-                next_token = self.scanner.scan();
+                next_token = next_token!(self);
                 if next_token.is(TokenKind::RightParen) {
-                    return Ok(let_form);
+                    return self.close(let_form, next_token);
                 }
             }
         });
     }
 
 
+    /// Read `(def name doc? attr-map? value?)`, the docstring and the attributes
+    /// are merged into the metadata of the Def.
     fn parse_def(&self, token: Token<'a>) -> ParseResult<'a> {
-        let def_ast = Ast::new_def(self.zone_allocator, token);        
-        let mut next_token = self.scanner.scan();
-        let binding: &'a Ast<'a>;
-        
-        match self.parse_literal(next_token) {
-            Ok(ast) => {
-                match ast {
-                    &Ast::Symbol(ref s) => {
-                        ast.set_symbol_mode(SymbolMode::Var(SymbolDepth::Origin));
-                        self.intern_scope(ast);
-                        binding = ast;
-                    }
-                    _ => {return Err(ParseError::new("The first argument of def must be a symbol.", next_token));}
-                }
-                def_ast.set_def_name(ast);
-            },
+        let def_ast = Ast::new_def(self.zone_allocator, token);
+        let name_token = next_token!(self);
+        let name = match self.parse_def_name(def_ast, name_token) {
+            Ok(name) => name,
             Err(e) => {return Err(e);}
-        }
+        };
 
-        next_token = self.scanner.scan();
-        match self.do_parse_form(def_ast, next_token, |ast: &'a Ast<'a>| {
-            def_ast.set_def_expr(ast);
-            binding.bind_to_symbol(ast);
-        }) {
+        let mut forms = Vec::new();
+        let close = match self.parse_body(def_ast, |ast: &'a Ast<'a>| forms.push(ast)) {
+            Ok(close) => close,
             Err(e) => {return Err(e);}
-            _ => {}
+        };
+        let (doc, attrs, value) = match (forms.len(), forms.first()) {
+            (0, _) => (None, None, None),
+            (1, _) => (None, None, Some(forms[0])),
+            (2, Some(&&Ast::String(_))) => (Some(forms[0]), None, Some(forms[1])),
+            (2, Some(&&Ast::Map(_))) => (None, Some(forms[0]), Some(forms[1])),
+            (3, Some(&&Ast::String(_))) if forms[1].is_map() => (Some(forms[0]), Some(forms[1]), Some(forms[2])),
+            _ => {
+                return Err(ParseError::new("def expected a name, an optional docstring and attr-map, and a value.", close));
+            }
+        };
+
+        match value {
+            Some(value) => {
+                def_ast.set_def_expr(value);
+                name.bind_to_symbol(value);
+            }
+            None => {}
         }
+        self.set_def_meta(def_ast, name, doc, attrs, false);
+        self.register_var(def_ast, name);
+        self.close(def_ast, close)
+    }
+
 
-        next_token = self.scanner.scan();
-        if next_token.kind() == TokenKind::RightParen {
-            return Ok(def_ast);
+    /// Read `(defn name doc? attr-map? [params] body)` or the multi-arity form
+    /// as `(def name (fn ...))`, the fn defined by defn- is private.
+    fn parse_defn(&self, token: Token<'a>, private: bool) -> ParseResult<'a> {
+        let def_ast = Ast::new_def(self.zone_allocator, token);
+        let name_token = next_token!(self);
+        let name = match self.parse_def_name(def_ast, name_token) {
+            Ok(name) => name,
+            Err(e) => {return Err(e);}
+        };
+
+        let mut next = next_token!(self);
+        let mut doc = None;
+        if next.is(TokenKind::String) {
+            match self.parse_literal(next) {
+                Ok(ast) => doc = Some(ast),
+                Err(e) => {return Err(e);}
+            }
+            next = next_token!(self);
+        }
+        let mut attrs = None;
+        if next.is(TokenKind::LeftBrace) {
+            match self.parse_map(next) {
+                Ok(ast) => attrs = Some(ast),
+                Err(e) => {return Err(e);}
+            }
+            next = next_token!(self);
         }
 
-        return Err(ParseError::new("Def accept 2 argument, ')' expected.", next_token));
+        let lambda = match self.parse_lambda_clauses(token, next) {
+            Ok(lambda) => lambda,
+            Err(e) => {return Err(e);}
+        };
+        def_ast.set_def_expr(lambda);
+        name.bind_to_symbol(lambda);
+        self.set_def_meta(def_ast, name, doc, attrs, private);
+        self.register_var(def_ast, name);
+        self.extend_to(def_ast, lambda);
+        Ok(def_ast)
     }
-    
 
-    fn parse_lambda(&self, token: Token<'a>) -> ParseResult<'a> {
-        return self.scope_handler.enter(|scope: &'a Scope<'a>| -> ParseResult<'a> {
-            let lambda = Ast::new_lambda(self.zone_allocator, token, scope);
-            
-            let mut token = self.scanner.scan();
-            if token.kind() != TokenKind::LeftBracket {
-                return Err(ParseError::new("Lambda expected parameter defintion as a vector.", token));
+
+    /// Read `(ns name doc? attr-map? references*)`, the namespace and the references,
+    /// `(:require ...)` and `(:import ...)`, are recorded on the ModuleInfo.
+    fn parse_ns(&self, token: Token<'a>) -> ParseResult<'a> {
+        if self.module_info.namespace().is_some() {
+            return Err(ParseError::new("ns can be declared only once in a module.", token));
+        }
+        let name_token = next_token!(self);
+        let name = match self.parse_name(name_token) {
+            Ok(ast) => ast,
+            Err(e) => {return Err(e);}
+        };
+        let namespace = match name.symbol_value() {
+            Some(value) if !value.contains('/') => value,
+            _ => {return Err(ParseError::new("The name of ns must be an unqualified symbol.", name_token));}
+        };
+        let ns = Ast::new_ns(self.zone_allocator, token, name);
+
+        let mut next = next_token!(self);
+        let mut doc = None;
+        if next.is(TokenKind::String) {
+            match self.parse_literal(next) {
+                Ok(ast) => doc = Some(ast),
+                Err(e) => {return Err(e);}
+            }
+            next = next_token!(self);
+        }
+        let mut attrs = None;
+        if next.is(TokenKind::LeftBrace) {
+            match self.parse_map(next) {
+                Ok(ast) => attrs = Some(ast),
+                Err(e) => {return Err(e);}
             }
+            next = next_token!(self);
+        }
+        self.set_def_meta(ns, name, doc, attrs, false);
 
-            let mut index = 0;
-            loop {
-                token = self.scanner.scan();
-                if token.kind() == TokenKind::RightBracket {
-                    break;
+        self.module_info.set_namespace(namespace);
+        self.namespace.set(namespace);
+        loop {
+            check_token!(self, ns, next, {
+                if next.is(TokenKind::RightParen) {
+                    return self.close(ns, next);
                 }
-                match self.do_parse_form(lambda, token, |ast: &'a Ast<'a>| {
-                    match ast {
-                        &Ast::Symbol(ref s) => {
-                            self.intern_scope(ast);
-                            ast.set_symbol_mode(SymbolMode::Parameter{index: index, depth: SymbolDepth::Origin});
-                        }
-                        _ => {}
-                    }
-                    lambda.add_lambda_arg(ast)
-                }) {
-                    Err(e) => {return Err(e);},
-                    _ => {}
+                let reference = match self.read_datum(next) {
+                    Ok(ast) => ast,
+                    Err(e) => {return Err(e);}
+                };
+                match self.read_ns_reference(reference) {
+                    Ok(()) => ns.add_ns_reference(reference),
+                    Err(e) => {return Err(e);}
                 }
-                index += 1;
-            }
+            });
+            next = next_token!(self);
+        }
+    }
 
-            token = self.scanner.scan();
 
-            loop {
-                match self.do_parse_form(lambda, token, |ast: &'a Ast<'a>| lambda.add_lambda_body(ast)) {
-                    Err(e) => {return Err(e);}
-                    _ => {}
+    /// Record the reference clause of ns such as `(:require [x :as y])` on the ModuleInfo.
+    fn read_ns_reference(&self, reference: &'a Ast<'a>) -> Result<(), ParseError<'a>> {
+        let token = reference.token().unwrap();
+        let (kind, specs) = match reference {
+            &Ast::List(_) => {
+                let children = reference.children().unwrap();
+                match children.first() {
+                    Some(&&Ast::Keyword(_)) => (children[0].string_value().unwrap(), children[1..].to_vec()),
+                    _ => {return Err(ParseError::new("ns expected a reference such as (:require ...).", token));}
                 }
-
-                token = self.scanner.scan();
-                if token.kind() == TokenKind::RightParen {
-                    return Ok(lambda);
+            }
+            _ => {return Err(ParseError::new("ns expected a reference such as (:require ...).", token));}
+        };
+
+        match kind {
+            ":require" => {
+                for spec in specs {
+                    match self.read_libspec(spec, "") {
+                        Err(e) => {return Err(e);}
+                        _ => {}
+                    }
                 }
             }
-        });
+            ":import" => {
+                for spec in specs {
+                    match self.read_import_spec(spec) {
+                        Ok(import) => self.module_info.add_import(import),
+                        Err(e) => {return Err(e);}
+                    }
+                }
+            }
+            ":refer-clojure" | ":gen-class" => {}
+            _ => {return Err(ParseError::new("Unsupported ns reference.", token));}
+        }
+        Ok(())
     }
 
 
-    fn parse_defmacro(&self) -> ParseResult<'a> {
-        return self.scope_handler.enter(|scope: &'a Scope<'a>| -> ParseResult<'a> {
-            let mut token = self.scanner.scan();
-
-            if token.kind() != TokenKind::Symbol {
-                return Err(ParseError::new("defmacro name expected symbol.", token));
+    /// Read `my.lib`, `[my.lib :as lib :refer [f g]]` or the prefix list
+    /// `(my [lib :as lib] other)` of `(:require ...)`.
+    fn read_libspec(&self, spec: &'a Ast<'a>, prefix: &str) -> Result<(), ParseError<'a>> {
+        let token = spec.token().unwrap();
+        match spec {
+            &Ast::Symbol(_) => {
+                let module = match self.namespace_name(spec, prefix) {
+                    Ok(module) => module,
+                    Err(e) => {return Err(e);}
+                };
+                self.module_info.add_require(Require::new(&module, None, Refer::Nothing));
+                Ok(())
             }
-
-            let defmacro;
-            match self.parse_literal(token) {
-                Ok(ast) => {
-                    match ast {
-                        &Ast::Symbol(ref s) => {
-                            ast.set_symbol_mode(SymbolMode::Var(SymbolDepth::Origin));
+            &Ast::Vector(_) => {
+                let children = spec.children().unwrap();
+                let module = match children.first() {
+                    Some(name) => match self.namespace_name(name, prefix) {
+                        Ok(module) => module,
+                        Err(e) => {return Err(e);}
+                    },
+                    None => {return Err(ParseError::new("require expected the name of the namespace.", token));}
+                };
+                if children.len() % 2 == 0 {
+                    return Err(ParseError::new("require expected option-value pairs after the namespace.", token));
+                }
+                let mut alias = None;
+                let mut refer = Refer::Nothing;
+                for pair in children[1..].chunks(2) {
+                    let option_token = pair[0].token().unwrap();
+                    match (pair[0].string_value(), pair[1]) {
+                        (Some(":as"), &Ast::Symbol(_)) => alias = pair[1].symbol_value(),
+                        (Some(":refer"), &Ast::Keyword(_)) if pair[1].string_value() == Some(":all") => refer = Refer::All,
+                        (Some(":refer"), &Ast::Vector(_)) => {
+                            let mut names = Vec::new();
+                            for name in pair[1].children().unwrap().iter() {
+                                match name.symbol_value() {
+                                    Some(value) if !value.contains('/') => names.push(value.to_string()),
+                                    _ => {return Err(ParseError::new(":refer expected unqualified symbols.", name.token().unwrap()));}
+                                }
+                            }
+                            refer = Refer::Names(names);
                         }
-                        _ => {return Err(ParseError::new("The first argument of defmacro must be a symbol.", token));}
+                        (Some(":as"), _) | (Some(":refer"), _) => {
+                            return Err(ParseError::new("Invalid value of the require option.", pair[1].token().unwrap()));
+                        }
+                        _ => {return Err(ParseError::new("Unsupported require option.", option_token));}
                     }
-                    defmacro = Ast::new_defmacro(self.zone_allocator, token, ast, scope);
-                    ast.bind_to_symbol(defmacro);
-                },
-                Err(e) => {return Err(e);}
-            }
-            
-            
-            let mut token = self.scanner.scan();
-            if token.kind() != TokenKind::LeftBracket {
-                return Err(ParseError::new("defmacro expected parameter defintion as a vector.", token));
+                }
+                self.module_info.add_require(Require::new(&module, alias, refer));
+                Ok(())
             }
-
-            let mut index = 0;
-            loop {
-                token = self.scanner.scan();
-                if token.kind() == TokenKind::RightBracket {
-                    break;
+            &Ast::List(_) => {
+                if !prefix.is_empty() {
+                    return Err(ParseError::new("Prefix lists cannot be nested.", token));
                 }
-                match self.do_parse_form(defmacro, token, |ast: &'a Ast<'a>| {
-                    match ast {
-                        &Ast::Symbol(ref s) => {
-                            self.intern_scope(ast);
-                            ast.set_symbol_mode(SymbolMode::Parameter{index: index, depth: SymbolDepth::Origin});
-                        }
+                let children = spec.children().unwrap();
+                let prefix = match children.first() {
+                    Some(name) => match self.namespace_name(name, "") {
+                        Ok(prefix) => prefix,
+                        Err(e) => {return Err(e);}
+                    },
+                    None => {return Err(ParseError::new("The prefix list expected the prefix of the namespaces.", token));}
+                };
+                for lib in children[1..].iter() {
+                    match self.read_libspec(lib, &prefix) {
+                        Err(e) => {return Err(e);}
                         _ => {}
                     }
-                    defmacro.add_macro_arg(ast)
-                }) {
-                    Err(e) => {return Err(e);},
-                    _ => {}
                 }
-                index += 1;
+                Ok(())
             }
+            &Ast::Keyword(_) => Ok(()),
+            _ => Err(ParseError::new("require expected a symbol, vector or prefix list.", token))
+        }
+    }
 
 
-            token = self.scanner.scan();
-            match self.do_parse_form(defmacro, token, |ast: &'a Ast<'a>| defmacro.add_macro_body(ast)) {
-                Err(e) => {return Err(e);}
-                _ => {}
+    /// Read `java.net.URL` or `[java.util Timer TimerTask]` of `(:import ...)`.
+    fn read_import_spec(&self, spec: &'a Ast<'a>) -> Result<Import, ParseError<'a>> {
+        let token = spec.token().unwrap();
+        match spec {
+            &Ast::Symbol(_) => {
+                let name = match self.namespace_name(spec, "") {
+                    Ok(name) => name,
+                    Err(e) => {return Err(e);}
+                };
+                match name.rfind('.') {
+                    Some(dot) => Ok(Import::new(&name[..dot], vec![name[dot + 1..].to_string()])),
+                    None => Err(ParseError::new("import expected a class name qualified with the package.", token))
+                }
             }
-
-            token = self.scanner.scan();
-            if token.kind() == TokenKind::RightParen {
-                return Ok(defmacro);
+            &Ast::Vector(_) | &Ast::List(_) => {
+                let children = spec.children().unwrap();
+                if children.len() < 2 {
+                    return Err(ParseError::new("import expected a package followed by the class names.", token));
+                }
+                let package = match self.namespace_name(children[0], "") {
+                    Ok(package) => package,
+                    Err(e) => {return Err(e);}
+                };
+                let mut classes = Vec::new();
+                for class in children[1..].iter() {
+                    match self.namespace_name(class, "") {
+                        Ok(name) => classes.push(name),
+                        Err(e) => {return Err(e);}
+                    }
+                }
+                Ok(Import::new(&package, classes))
             }
-
-            Err(ParseError::new("defmacro close paren [)] expected.", token))
-        });
-    }
-
-
-    fn parse_if(&self, if_form: &'a Ast<'a>) -> ParseResult<'a> {
-        let mut token = self.scanner.scan();
-        match self.do_parse_form(if_form, token, |ast: &'a Ast<'a>| if_form.set_cond(ast)) {
-            Err(e) => {return Err(e);}
-            _ => {}
+            _ => Err(ParseError::new("import expected a symbol, vector or list.", token))
         }
+    }
 
-        token = self.scanner.scan();
-        match self.do_parse_form(if_form, token, |ast: &'a Ast<'a>| if_form.set_then_body(ast)) {
-            Err(e) => {return Err(e);}
-            _ => {}
-        }
 
-        token = self.scanner.scan();
-        if token.kind() != TokenKind::RightParen {
-            match self.do_parse_form(if_form, token, |ast: &'a Ast<'a>| if_form.set_else_body(ast)) {
-                Err(e) => {return Err(e);},
-                _ => {}
+    /// The name of the namespace written as the symbol `name`, joined to `prefix` if any.
+    fn namespace_name(&self, name: &'a Ast<'a>, prefix: &str) -> Result<std::string::String, ParseError<'a>> {
+        match name.symbol_value() {
+            Some(value) if !value.contains('/') => {
+                if prefix.is_empty() {
+                    Ok(value.to_string())
+                } else {
+                    Ok(format!("{}.{}", prefix, value))
+                }
             }
+            _ => Err(ParseError::new("Expected an unqualified symbol.", name.token().unwrap()))
         }
+    }
 
-        token = self.scanner.scan();
 
-        if token.kind() == TokenKind::RightParen {
-            return Ok(if_form);
+    /// Read the name of def or defn and bind it in the current scope.
+    fn parse_def_name(&self, def_ast: &'a Ast<'a>, token: Token<'a>) -> ParseResult<'a> {
+        match self.parse_name(token) {
+            Ok(ast) => {
+                match ast {
+                    &Ast::Symbol(ref s) => {
+                        if self.builtin_token_registry.special_form(s.token().value()).is_some() {
+                            return Err(ParseError::new("Special forms cannot be redefined.", s.token()));
+                        }
+                        ast.set_symbol_mode(SymbolMode::Var(SymbolDepth::Origin));
+                        self.intern_scope(ast);
+                    }
+                    _ => {return Err(ParseError::new("The first argument of def must be a symbol.", token));}
+                }
+                def_ast.set_def_name(ast);
+                Ok(ast)
+            },
+            Err(e) => Err(e)
         }
-
-        Err(ParseError::new("')' expected.", token))
     }
 
 
-    fn parse_quote(&self, quote: &'a Ast<'a>) -> ParseResult<'a> {
-        let mut token = self.scanner.scan();
-        match self.do_parse_form(quote, token, |ast: &'a Ast<'a>| quote.set_quote_expr(ast)) {
-            Err(e) => {return Err(e)},
-            _ => {}
+    /// Record the definition on the ModuleInfo, the private one cannot be referred by the other modules.
+    fn register_var(&self, definition: &'a Ast<'a>, name: &'a Ast<'a>) {
+        self.module_info.add_var(name.symbol_value().unwrap());
+        if definition.is_private() {
+            self.module_info.add_private_var(name.symbol_value().unwrap());
         }
+    }
 
-        token = self.scanner.scan();
 
-        if token.kind() == TokenKind::RightParen {
-            return Ok(quote);
+    /// Set the metadata of def or defmacro, merged from the metadata of the name,
+    /// the attr-map, the docstring and the privacy in this order.
+    fn set_def_meta(&self, definition: &'a Ast<'a>, name: &'a Ast<'a>, doc: Option<&'a Ast<'a>>,
+                    attrs: Option<&'a Ast<'a>>, private: bool) {
+        let token = definition.token().unwrap();
+        let entries = Ast::new_map(self.zone_allocator, token);
+        match doc {
+            Some(doc) => {
+                entries.add_child(self.new_builtin_keyword(token, ":doc"));
+                entries.add_child(doc);
+            }
+            None => {}
+        }
+        if private {
+            entries.add_child(self.new_builtin_keyword(token, ":private"));
+            entries.add_child(Ast::new_boolean(self.zone_allocator, token, true));
         }
 
-        Err(ParseError::new("quote expected only one argument.", token))
+        let mut meta = name.meta();
+        for outer in attrs.iter().chain(Some(entries).iter()) {
+            if outer.children().unwrap().is_empty() {
+                continue;
+            }
+            meta = match meta {
+                Some(inner) => Some(self.merge_meta(inner, outer)),
+                None => Some(*outer)
+            };
+        }
+        match meta {
+            Some(meta) => definition.set_meta(meta),
+            None => {}
+        }
     }
 
 
-    fn do_parse_form<T>(&self, form: &'a Ast<'a>, token: Token<'a>, mut add: T) -> ParseResult<'a> where
-        T: FnMut(&'a Ast<'a>) {
-        let next = token;
-        check_token!(form, next, {
-            match next.kind() {
-                TokenKind::LeftParen => {
-                    match self.parse_form(next) {
-                        Ok(ast) => add(ast),
-                        Err(e) => {return Result::Err(e);}
-                    }
-                }
-                TokenKind::ShortLambdaBegin => {
-                    match self.parse_short_lambda(next) {
-                        Ok(ast) => add(ast),
-                        Err(e) => {return Result::Err(e);}
-                    }
+    /// Read `(fn name? [params] body)` or `(fn name? ([params] body) ...)`,
+    /// the name is bound to the fn in its own scope which encloses the parameters.
+    fn parse_lambda(&self, token: Token<'a>) -> ParseResult<'a> {
+        let next = next_token!(self);
+        if !next.is(TokenKind::Symbol) {
+            return self.parse_lambda_clauses(token, next);
+        }
+        return self.scope_handler.enter(|scope: &'a Scope<'a>| -> ParseResult<'a> {
+            let value = get_token_value!(self, next);
+            if value.contains('/') {
+                return Err(ParseError::new("The name of fn must be an unqualified symbol.", next));
+            }
+            let name = Ast::new_symbol(self.zone_allocator, next, value, SymbolMode::Var(SymbolDepth::Origin));
+            scope.intern(name);
+
+            let clauses = next_token!(self);
+            match self.parse_lambda_clauses(token, clauses) {
+                Ok(lambda) => {
+                    lambda.set_lambda_name(name);
+                    name.bind_to_symbol(lambda);
+                    Ok(lambda)
                 }
-                TokenKind::LeftBracket => {
-                    match self.parse_vector(next) {
-                        Ok(ast) => add(ast),
-                        Err(e) => {return Result::Err(e);}
+                Err(e) => Err(e)
+            }
+        });
+    }
+
+
+    fn parse_lambda_clauses(&self, token: Token<'a>, next: Token<'a>) -> ParseResult<'a> {
+        match next.kind() {
+            TokenKind::LeftBracket => self.parse_lambda_clause(token),
+            TokenKind::LeftParen => self.parse_multi_arity_lambda(token, next),
+            _ => Err(ParseError::new("Lambda expected parameter defintion as a vector.", next))
+        }
+    }
+
+
+    /// Read the arity clauses `([x] ...) ([x y] ...) ([x y & more] ...)`.
+    /// The fixed arities must differ and at most one clause is variadic,
+    /// which must have no less fixed parameters than any other clause.
+    fn parse_multi_arity_lambda(&self, token: Token<'a>, first: Token<'a>) -> ParseResult<'a> {
+        return self.scope_handler.enter(|scope: &'a Scope<'a>| -> ParseResult<'a> {
+            let lambda = Ast::new_lambda(self.zone_allocator, token, scope);
+            scope.set_origin(lambda);
+
+            let mut fixed: Vec<usize> = Vec::new();
+            let mut variadic: Option<usize> = None;
+            let mut next = first;
+            loop {
+                check_token!(self, lambda, next, {
+                    match next.kind() {
+                        TokenKind::RightParen => {
+                            return self.close(lambda, next);
+                        }
+                        TokenKind::LeftParen => {}
+                        _ => {
+                            return Err(ParseError::new("fn expected an arity clause such as ([x] body).", next));
+                        }
                     }
+                });
+                let params = next_token!(self);
+                if !params.is(TokenKind::LeftBracket) {
+                    return Err(ParseError::new("Lambda expected parameter defintion as a vector.", params));
                 }
-                TokenKind::LeftBrace => {
-                    match self.parse_map(next) {
-                        Ok(ast) => add(ast),
-                        Err(e) => {return Result::Err(e);}
+                let clause = match self.parse_lambda_clause(next) {
+                    Ok(clause) => clause,
+                    Err(e) => {return Err(e);}
+                };
+
+                let (arity, rest) = clause.arity().unwrap();
+                if rest {
+                    if variadic.is_some() {
+                        return Err(ParseError::new("fn can have only one variadic arity clause.", next));
                     }
-                }
-                TokenKind::Tag => {
-                    match self.parse_tag(next) {
-                        Ok(ast) => add(ast),
-                        Err(e) => {return Result::Err(e);}
+                    variadic = Some(arity);
+                } else {
+                    if fixed.contains(&arity) {
+                        return Err(ParseError::new("Duplicate arity clause.", next));
                     }
+                    fixed.push(arity);
                 }
-                _ => {
-                    match self.parse_literal(next) {
-                        Ok(ast) => add(ast),
-                        Err(e) => {return Result::Err(e);}
+                match variadic {
+                    Some(v) => {
+                        if fixed.iter().any(|&f| f > v) {
+                            return Err(ParseError::new("Fixed arity clause cannot have more parameters than the variadic clause.", next));
+                        }
                     }
+                    None => {}
                 }
+                lambda.add_lambda_clause(clause);
+                next = next_token!(self);
             }
         });
-        Result::Ok(form)
     }
 
 
-    fn parse_vector(&self, token: Token<'a>) -> ParseResult<'a> {
-        let vector = Ast::new_vector(self.zone_allocator, token);
-        loop {
-            let next = self.scanner.scan();
-            check_token!(vector, next, {
-                match next.kind() {
-                    TokenKind::RightBracket => {
-                        return Result::Ok(vector);
-                    }
-                    _ => {
-                        match self.do_parse_form(vector, next, |ast: &'a Ast<'a>| vector.add_child(ast)) {
-                            Ok(ast) => {},
-                            Err(e) => {return Result::Err(e);}
-                        }
-                    }
+    /// Read the parameters after `[` and the body until `)` as a Lambda of single arity.
+    fn parse_lambda_clause(&self, token: Token<'a>) -> ParseResult<'a> {
+        return self.scope_handler.enter(|scope: &'a Scope<'a>| -> ParseResult<'a> {
+            let lambda = Ast::new_lambda(self.zone_allocator, token, scope);
+            
+            scope.set_origin(lambda);
+
+            let destructuring = match self.parse_parameters(lambda, |ast: &'a Ast<'a>| lambda.add_lambda_arg(ast)) {
+                Ok(destructuring) => destructuring,
+                Err(e) => {return Err(e);}
+            };
+
+            if destructuring.is_empty() {
+                return match self.parse_body(lambda, |ast: &'a Ast<'a>| lambda.add_lambda_body(ast)) {
+                    Ok(close) => self.close(lambda, close),
+                    Err(e) => Err(e)
+                };
+            }
+            match self.destructure(lambda.token().unwrap(), &destructuring, |let_form: &'a Ast<'a>| {
+                self.parse_body(let_form, |ast: &'a Ast<'a>| let_form.add_let_body(ast))
+            }) {
+                Ok((let_form, close)) => {
+                    lambda.add_lambda_body(let_form);
+                    self.close(lambda, close)
                 }
-            })
-        }
+                Err(e) => Err(e)
+            }
+        });
     }
 
 
-    fn parse_map(&self, token: Token<'a>) -> ParseResult<'a> {
-        let map = Ast::new_map(self.zone_allocator, token);
+    /// Read the forms until `)` and return the `)`.
+    fn parse_body<T>(&self, owner: &'a Ast<'a>, mut add: T) -> Result<Token<'a>, ParseError<'a>> where
+        T: FnMut(&'a Ast<'a>) {
         loop {
-            let next = self.scanner.scan();
-            check_token!(map, next, {
-                match next.kind() {
-                    TokenKind::RightBrace => {
-                        match map.children() {
-                            Some(children) => {
-                                if children.len() % 2 != 0 {
-                                    match map.token() {
-                                        Some(token) => {
-                                            return Result::Err(ParseError::new("map expected key-value pair.", token));
-                                        }
-                                        _ => {}
-                                    }
-                                }
-                            },
-                            _ => {}
-                        }
-                        return Result::Ok(map);
-                    }
-                    _ => {
-                        match self.do_parse_form(map, next, |ast: &'a Ast<'a>| map.add_child(ast)) {
-                            Ok(ast) => {},
-                            Err(e) => {return Result::Err(e);}
-                        }
-                    }
-                }
-            })
+            let token = next_token!(self);
+            if token.is(TokenKind::RightParen) {
+                return Result::Ok(token);
+            }
+            match self.do_parse_form(owner, token, |ast: &'a Ast<'a>| add(ast)) {
+                Err(e) => {return Err(e);}
+                _ => {}
+            }
         }
     }
 
 
-    fn parse_tag(&self, token: Token<'a>) -> ParseResult<'a> {
-        let form = Ast::new_tag(self.zone_allocator, token);
-        let mut next = self.scanner.scan();
+    fn parse_defmacro(&self, form_token: Token<'a>) -> ParseResult<'a> {
+        return self.scope_handler.enter(|scope: &'a Scope<'a>| -> ParseResult<'a> {
+            let token = next_token!(self);
 
-        match self.do_parse_form(form, next, |ast: &'a Ast<'a>| form.add_child(ast)) {
-            Ok(ast) => {},
-            Err(e) => {return Result::Err(e)}
-        }
-        Result::Ok(form)
-    }
-    
+            check_token!(self, token, token, {
+                if token.kind() != TokenKind::Symbol && token.kind() != TokenKind::Tag {
+                    return Err(ParseError::new("defmacro name expected symbol.", token));
+                }
+            });
 
-    fn parse_short_lambda(&self, token: Token<'a>) -> ParseResult<'a> {
-        let lambda = Ast::new_lambda_sugar(self.zone_allocator, token);
-        loop {
-            let next = self.scanner.scan();
-            check_token!(lambda, next, {
-                match next.kind() {
-                    TokenKind::ParamName => {
-                        let s = get_token_value!(self, next);
-                        match s.parse::<i32>() {
-                            Ok(i) => {
-                                lambda.add_child(Ast::new_lambda_param(self.zone_allocator, next, i));
-                            },
-                            Err(_) => {
-                                return Result::Err(ParseError::new("Invalid Lambda parameter.", next));
+            let defmacro;
+            let name;
+            match self.parse_name(token) {
+                Ok(ast) => {
+                    match ast {
+                        &Ast::Symbol(ref s) => {
+                            if self.builtin_token_registry.special_form(s.token().value()).is_some() {
+                                return Err(ParseError::new("Special forms cannot be redefined.", s.token()));
                             }
+                            ast.set_symbol_mode(SymbolMode::Var(SymbolDepth::Origin));
                         }
+                        _ => {return Err(ParseError::new("The first argument of defmacro must be a symbol.", token));}
                     }
-                    TokenKind::RightParen => {
-                        return Result::Ok(lambda);
-                    }
-                    _ => {
-                        match self.do_parse_form(lambda, next, |ast: &'a Ast<'a>| lambda.add_child(ast)) {
-                            Ok(ast) => {},
-                            Err(e) => {return Result::Err(e);}
-                        }
-                    }
-                }
-            })
-        }
-    }
-
-
-    fn parse_literal(&self, token: Token<'a>) -> ParseResult<'a> {
-        match token.kind() {
-            TokenKind::Keyword => {
-                Ok(Ast::new_keyword(self.zone_allocator, token, get_token_value!(self, token)))
+                    name = ast;
+                    defmacro = Ast::new_defmacro(self.zone_allocator, form_token, ast, scope);
+                    ast.bind_to_symbol(defmacro);
+                    scope.set_origin(defmacro);
+                },
+                Err(e) => {return Err(e);}
             }
-            TokenKind::Hex|
-            TokenKind::Binary|
-            TokenKind::Int => {
-                match get_token_value!(self, token).parse::<i32>() {
-                    Ok(i) => Ok(Ast::new_integer(self.zone_allocator, token, i)),
-                    Err(_) => {
-                        return Result::Err(ParseError::new("Invalid integer value.", token));
-                    }
+
+            let mut token = next_token!(self);
+            let mut doc = None;
+            if token.is(TokenKind::String) {
+                match self.parse_literal(token) {
+                    Ok(ast) => doc = Some(ast),
+                    Err(e) => {return Err(e);}
                 }
+                token = next_token!(self);
             }
-            TokenKind::Long|
-            TokenKind::Float => {
-                match get_token_value!(self, token).parse::<f64>() {
-                    Ok(i) => Ok(Ast::new_double(self.zone_allocator, token, i)),
-                    Err(_) => {
-                        return Result::Err(ParseError::new("Invalid double value.", token));
-                    }
+            let mut attrs = None;
+            if token.is(TokenKind::LeftBrace) {
+                match self.parse_map(token) {
+                    Ok(ast) => attrs = Some(ast),
+                    Err(e) => {return Err(e);}
                 }
+                token = next_token!(self);
             }
-            TokenKind::String => {
-                Ok(Ast::new_string(self.zone_allocator, token, get_token_value!(self, token)))
-            }
-            TokenKind::Regexp => {
-                Ok(Ast::new_regexp(self.zone_allocator, token, get_token_value!(self, token)))
-            }
-            TokenKind::Symbol => {
-                return self.process_sym(token);
-            }
-            TokenKind::Boolean => {
-                if get_token_value!(self, token) == "true" {
-                    return Ok(Ast::new_boolean(self.zone_allocator, token, true));
+            self.set_def_meta(defmacro, name, doc, attrs, false);
+            self.register_var(defmacro, name);
+
+            check_token!(self, defmacro, token, {
+                if token.kind() != TokenKind::LeftBracket {
+                    return Err(ParseError::new("defmacro expected parameter defintion as a vector.", token));
                 }
-                return Ok(Ast::new_boolean(self.zone_allocator, token, false));
+            });
+
+            let destructuring = match self.parse_parameters(defmacro, |ast: &'a Ast<'a>| defmacro.add_macro_arg(ast)) {
+                Ok(destructuring) => destructuring,
+                Err(e) => {return Err(e);}
+            };
+
+            if destructuring.is_empty() {
+                return match self.parse_macro_body(defmacro, |ast: &'a Ast<'a>| defmacro.add_macro_body(ast)) {
+                    Ok(close) => self.close(defmacro, close),
+                    Err(e) => Err(e)
+                };
             }
-            TokenKind::UnicodeChar => {
-                let v = get_token_value!(self, token);
-                match self.parse_unicode_escape_seq(v) {
-                    Ok(i) => Ok(Ast::new_uchar(self.zone_allocator, token, i)),
-                    Err(e) => Err(ParseError::new(e, token))
+            match self.destructure(form_token, &destructuring, |let_form: &'a Ast<'a>| {
+                self.parse_macro_body(let_form, |ast: &'a Ast<'a>| let_form.add_let_body(ast))
+            }) {
+                Ok((let_form, close)) => {
+                    defmacro.add_macro_body(let_form);
+                    self.close(defmacro, close)
                 }
+                Err(e) => Err(e)
             }
-            TokenKind::Nil => {
-                Ok(Ast::new_nil(self.zone_allocator, token))
+        });
+    }
+
+
+    /// Read the only body form of defmacro and return the `)`.
+    fn parse_macro_body<T>(&self, owner: &'a Ast<'a>, add: T) -> Result<Token<'a>, ParseError<'a>> where
+        T: FnMut(&'a Ast<'a>) {
+        let mut token = next_token!(self);
+        match self.do_parse_form(owner, token, add) {
+            Err(e) => {return Err(e);}
+            _ => {}
+        }
+
+        token = next_token!(self);
+        if token.kind() == TokenKind::RightParen {
+            return Result::Ok(token);
+        }
+
+        Err(ParseError::new("defmacro close paren [)] expected.", token))
+    }
+
+
+    /// Read the parameter vector of fn or defmacro after `[`.
+    /// The fixed parameters are passed to `add` and the parameter following `&`
+    /// is set as the rest parameter of `owner`. A vector or map parameter is replaced
+    /// with a generated parameter, which is returned with the binding form to destructure.
+    fn parse_parameters<T>(&self, owner: &'a Ast<'a>, mut add: T) -> Result<Destructuring<'a>, ParseError<'a>> where
+        T: FnMut(&'a Ast<'a>) {
+        let mut destructuring = Vec::new();
+        let mut index = 0;
+        loop {
+            let token = next_token!(self);
+            if token.kind() == TokenKind::RightBracket {
+                return Result::Ok(destructuring);
             }
-            TokenKind::QuoteRm => {
-                let q = Ast::new_quote(self.zone_allocator, token);
-                let next_token = self.scanner.scan();
-                return match self.do_parse_form(q, next_token, |ast: &'a Ast<'a>| q.set_quote_expr(ast)) {
-                    Err(e) => Err(e),
-                    Ok(ast) => Ok(q)
+            if token.is(TokenKind::Symbol) && get_token_value!(self, token) == "&" {
+                return match self.parse_rest_parameter(owner, token, index, &mut destructuring) {
+                    Ok(_) => Result::Ok(destructuring),
+                    Err(e) => Result::Err(e)
                 };
             }
-            _ => {
-                Err(ParseError::new("Invalid Token.", token))
+            let mode = SymbolMode::Parameter{index: index, depth: SymbolDepth::Origin};
+            match self.parse_parameter(owner, token, mode, &mut destructuring) {
+                Ok(param) => add(param),
+                Err(e) => {return Err(e);}
             }
+            index += 1;
         }
     }
 
 
-    fn process_sym(&self, token: Token<'a>) -> ParseResult<'a> {
-        let v = get_token_value!(self, token);
-        let sp: Vec<&'a str> = v.split('/').collect();
-        if sp.len() == 1 {
-            let sym = Ast::new_symbol(self.zone_allocator, token, v, SymbolMode::Unresolved);
-            match self.find_scope(sym) {
-                Some((d, s)) => {
-                    match s.symbol_mode() {
-                        SymbolMode::Parameter{index, depth} => {
-                            sym.set_symbol_mode(SymbolMode::Parameter {index: index, depth: SymbolDepth::Depth(d)});
-                        },
-                        _ => {sym.set_symbol_mode(SymbolMode::Var(SymbolDepth::Depth(d)));}
-                    }
-                }
-                None => {}
+    /// Read `& rest]`, exactly one parameter follows `&`.
+    fn parse_rest_parameter(&self, owner: &'a Ast<'a>, amp: Token<'a>, index: i32,
+                            destructuring: &mut Destructuring<'a>) -> Result<(), ParseError<'a>> {
+        let token = next_token!(self);
+        check_token!(self, owner, token, {
+            if token.is(TokenKind::RightBracket) || (token.is(TokenKind::Symbol) && get_token_value!(self, token) == "&") {
+                return Result::Err(ParseError::new("& expected a rest parameter.", amp));
             }
-            return Ok(sym);
+        });
+        let mode = SymbolMode::RestParameter{index: index, depth: SymbolDepth::Origin};
+        match self.parse_parameter(owner, token, mode, destructuring) {
+            Ok(rest) => owner.set_rest_parameter(rest),
+            Err(e) => {return Err(e);}
         }
 
-        let mr = Ast::new_module_reference(self.zone_allocator, token);
-        for ns in sp {
-            mr.add_child(Ast::new_symbol(self.zone_allocator, token, ns, SymbolMode::Var(SymbolDepth::Depth(0))));
-        }
-        return Ok(mr);
+        let close = next_token!(self);
+        check_token!(self, owner, close, {
+            if !close.is(TokenKind::RightBracket) {
+                return Result::Err(ParseError::new("Only one parameter can follow &.", close));
+            }
+        });
+        Result::Ok(())
     }
 
 
-    fn parse_unicode_escape_seq(&self, ue: &'a str) -> Result<i32, &'static str> {
-        let mut result: i32 = 0;
-        for u in ue.bytes() {
-            let v = self.to_hex_value(u);
-            if v < 0 {
-                return Result::Err("Invalid unicode sequence.");
+    /// Read a parameter and bind it in the current scope.
+    fn parse_parameter(&self, owner: &'a Ast<'a>, token: Token<'a>, mode: SymbolMode,
+                       destructuring: &mut Destructuring<'a>) -> ParseResult<'a> {
+        let form = match self.read_binding_form(token) {
+            Ok(form) => form,
+            Err(e) => {return Err(e);}
+        };
+        let param = match form {
+            BindingForm::Symbol(sym) => sym,
+            _ => {
+                let param = self.new_gensym(token, "p");
+                destructuring.push((form, param));
+                param
             }
-            result += result * 16 + v;
-        }
-        Ok(result)
+        };
+        param.set_symbol_mode(mode);
+        self.intern_scope(param);
+        Result::Ok(param)
     }
 
 
-    fn to_hex_value(&self, uchar: u8) -> i32 {
-        if uchar >= '0' as u8 && uchar <= '9' as u8 {
-            return (uchar as i32 - '0' as i32) as i32;
-        } else if uchar >= 'a' as u8 && uchar <= 'f' as u8 {
-            return (uchar as i32 - 'a' as i32 + 10) as i32;
-        } else if uchar >= 'A' as u8 && uchar <= 'F' as u8 {
-            return (uchar as i32 - 'A' as i32 + 10);
+    /// Read a binding form, a symbol or a vector or map to destructure.
+    fn read_binding_form(&self, token: Token<'a>) -> Result<BindingForm<'a>, ParseError<'a>> {
+        match token.kind() {
+            TokenKind::Symbol => {
+                let name = get_token_value!(self, token);
+                if name == "&" || name.contains('/') {
+                    return Result::Err(ParseError::new("Invalid binding form.", token));
+                }
+                Result::Ok(BindingForm::Symbol(Ast::new_symbol(self.zone_allocator, token, name, SymbolMode::Unresolved)))
+            }
+            TokenKind::ParamName if self.short_lambda.borrow().is_none() => {
+                Result::Ok(BindingForm::Symbol(self.new_builtin_symbol(token, get_token_value!(self, token))))
+            }
+            TokenKind::Tag => self.read_tagged_binding(token),
+            TokenKind::LeftBracket => self.read_sequential_binding(token),
+            TokenKind::LeftBrace => self.read_associative_binding(token),
+            TokenKind::Eof => Result::Err(ParseError::new_incomplete("Unexpected end of input", token)),
+            TokenKind::Invalid => Result::Err(self.invalid_token(token)),
+            _ => Result::Err(ParseError::new("Invalid binding form.", token))
         }
-        return -1;
     }
 
 
-    fn find_scope(&self, ast: &'a Ast<'a>) -> Option<(u32, &'a Ast<'a>)> {
-        self.scope_handler.scope().find(ast)
+    /// Read `^meta form` of a binding form, the metadata is attached to the bound symbol
+    /// or to the symbol which the destructured value is bound to.
+    fn read_tagged_binding(&self, token: Token<'a>) -> Result<BindingForm<'a>, ParseError<'a>> {
+        let entries = match self.read_meta(token) {
+            Ok(entries) => entries,
+            Err(e) => {return Result::Err(e);}
+        };
+        let next = next_token!(self);
+        match self.read_binding_form(next) {
+            Ok(BindingForm::Symbol(sym)) => {
+                sym.set_meta(self.add_meta(sym.meta(), entries));
+                Result::Ok(BindingForm::Symbol(sym))
+            }
+            Ok(BindingForm::Sequential {token, elements, rest, whole, meta}) => {
+                Result::Ok(BindingForm::Sequential {
+                    token: token,
+                    elements: elements,
+                    rest: rest,
+                    whole: whole,
+                    meta: Some(self.add_meta(meta, entries))
+                })
+            }
+            Ok(BindingForm::Associative {token, entries: bindings, defaults, whole, meta}) => {
+                Result::Ok(BindingForm::Associative {
+                    token: token,
+                    entries: bindings,
+                    defaults: defaults,
+                    whole: whole,
+                    meta: Some(self.add_meta(meta, entries))
+                })
+            }
+            Err(e) => Result::Err(e)
+        }
     }
 
 
-    fn intern_scope(&self, ast: &'a Ast<'a>) {
-        self.scope_handler.scope().intern(ast);
+    fn read_sequential_binding(&self, token: Token<'a>) -> Result<BindingForm<'a>, ParseError<'a>> {
+        let mut elements = Vec::new();
+        let mut rest = None;
+        let mut whole = None;
+        loop {
+            let next = next_token!(self);
+            if next.is(TokenKind::RightBracket) {
+                break;
+            }
+            if whole.is_some() {
+                return Result::Err(ParseError::new(":as must be the last of the binding form.", next));
+            }
+            if next.is(TokenKind::Keyword) && get_token_value!(self, next) == ":as" {
+                match self.read_binding_symbol(next) {
+                    Ok(sym) => whole = Some(sym),
+                    Err(e) => {return Err(e);}
+                }
+                continue;
+            }
+            if rest.is_some() {
+                return Result::Err(ParseError::new("Only one binding form can follow &.", next));
+            }
+            if next.is(TokenKind::Symbol) && get_token_value!(self, next) == "&" {
+                let form = next_token!(self);
+                if form.is(TokenKind::RightBracket) {
+                    return Result::Err(ParseError::new("& expected a binding form.", next));
+                }
+                match self.read_binding_form(form) {
+                    Ok(form) => rest = Some(Box::new(form)),
+                    Err(e) => {return Err(e);}
+                }
+                continue;
+            }
+            match self.read_binding_form(next) {
+                Ok(form) => elements.push(form),
+                Err(e) => {return Err(e);}
+            }
+        }
+        Result::Ok(BindingForm::Sequential {
+            token: token,
+            elements: elements,
+            rest: rest,
+            whole: whole,
+            meta: None
+        })
+    }
+
+
+    fn read_associative_binding(&self, token: Token<'a>) -> Result<BindingForm<'a>, ParseError<'a>> {
+        let mut entries = Vec::new();
+        let mut defaults = Vec::new();
+        let mut whole = None;
+        loop {
+            let next = next_token!(self);
+            if next.is(TokenKind::RightBrace) {
+                break;
+            }
+            let directive = if next.is(TokenKind::Keyword) {get_token_value!(self, next)} else {""};
+            match directive {
+                ":as" => {
+                    match self.read_binding_symbol(next) {
+                        Ok(sym) => whole = Some(sym),
+                        Err(e) => {return Err(e);}
+                    }
+                }
+                ":keys" => {
+                    let vector = next_token!(self);
+                    if !vector.is(TokenKind::LeftBracket) {
+                        return Result::Err(ParseError::new(":keys expected a vector of symbols.", next));
+                    }
+                    loop {
+                        let key = next_token!(self);
+                        if key.is(TokenKind::RightBracket) {
+                            break;
+                        }
+                        match self.read_binding_form(key) {
+                            Ok(BindingForm::Symbol(sym)) => {
+                                let keyword = self.new_builtin_keyword(key, &format!(":{}", get_token_value!(self, sym.token().unwrap())));
+                                entries.push((BindingForm::Symbol(sym), keyword));
+                            }
+                            Ok(_) => {return Result::Err(ParseError::new(":keys expected a vector of symbols.", key));}
+                            Err(e) => {return Err(e);}
+                        }
+                    }
+                }
+                ":or" => {
+                    let map = next_token!(self);
+                    if !map.is(TokenKind::LeftBrace) {
+                        return Result::Err(ParseError::new(":or expected a map of the default values.", next));
+                    }
+                    loop {
+                        let name = next_token!(self);
+                        if name.is(TokenKind::RightBrace) {
+                            break;
+                        }
+                        let sym = match self.read_binding_form(name) {
+                            Ok(BindingForm::Symbol(sym)) => sym,
+                            Ok(_) => {return Result::Err(ParseError::new(":or expected a map of the default values.", name));}
+                            Err(e) => {return Err(e);}
+                        };
+                        match self.read_form(name) {
+                            Ok(value) => defaults.push((sym, value)),
+                            Err(e) => {return Err(e);}
+                        }
+                    }
+                }
+                _ => {
+                    let form = match self.read_binding_form(next) {
+                        Ok(form) => form,
+                        Err(e) => {return Err(e);}
+                    };
+                    match self.read_form(next) {
+                        Ok(key) => entries.push((form, key)),
+                        Err(e) => {return Err(e);}
+                    }
+                }
+            }
+        }
+        Result::Ok(BindingForm::Associative {
+            token: token,
+            entries: entries,
+            defaults: defaults,
+            whole: whole,
+            meta: None
+        })
+    }
+
+
+    /// Read the symbol following `:as`.
+    fn read_binding_symbol(&self, token: Token<'a>) -> ParseResult<'a> {
+        let next = next_token!(self);
+        match self.read_binding_form(next) {
+            Ok(BindingForm::Symbol(sym)) => Result::Ok(sym),
+            Ok(_) => Result::Err(ParseError::new(":as expected a symbol.", token)),
+            Err(e) => Result::Err(e)
+        }
+    }
+
+
+    /// Bind the destructured parameters by a let, which is created in a new scope
+    /// and wraps the body of fn or defmacro read by `parse_body`.
+    fn destructure<T>(&self, token: Token<'a>, destructuring: &Destructuring<'a>, mut parse_body: T)
+                      -> Result<(&'a Ast<'a>, Token<'a>), ParseError<'a>> where
+        T: FnMut(&'a Ast<'a>) -> Result<Token<'a>, ParseError<'a>> {
+        self.scope_handler.enter(|scope: &'a Scope<'a>| {
+            let let_form = Ast::new_let(self.zone_allocator, token, scope);
+            for &(ref form, param) in destructuring.iter() {
+                let value = self.new_reference(token, param);
+                self.bind_form(let_form, scope, form, value);
+            }
+            match parse_body(let_form) {
+                Ok(close) => {
+                    let_form.extend_source_info(close.info());
+                    Result::Ok((let_form, close))
+                }
+                Err(e) => Result::Err(e)
+            }
+        })
+    }
+
+
+    /// Desugar the binding form into the let bindings of the symbols,
+    /// `[a & r]` is bound by `nth` and `nthnext`, `{a :a}` by `get`.
+    fn bind_form(&self, let_form: &'a Ast<'a>, scope: &'a Scope<'a>, form: &BindingForm<'a>, value: &'a Ast<'a>) {
+        match form {
+            &BindingForm::Symbol(sym) => {
+                self.bind_symbol(let_form, scope, sym, value);
+            }
+            &BindingForm::Sequential {token, ref elements, ref rest, whole, meta} => {
+                let seq = self.new_gensym(token, "vec");
+                match meta {
+                    Some(meta) => seq.set_meta(meta),
+                    None => {}
+                }
+                self.bind_symbol(let_form, scope, seq, value);
+                match whole {
+                    Some(sym) => self.bind_symbol(let_form, scope, sym, self.new_reference(token, seq)),
+                    None => {}
+                }
+                for (i, element) in elements.iter().enumerate() {
+                    let nth = self.new_call(token, "nth", vec![self.new_reference(token, seq),
+                                                               self.new_integer(token, i as i64),
+                                                               Ast::new_nil(self.zone_allocator, token)]);
+                    self.bind_form(let_form, scope, element, nth);
+                }
+                match rest {
+                    &Some(ref rest) => {
+                        let nthnext = self.new_call(token, "nthnext", vec![self.new_reference(token, seq),
+                                                                           self.new_integer(token, elements.len() as i64)]);
+                        self.bind_form(let_form, scope, rest, nthnext);
+                    }
+                    &None => {}
+                }
+            }
+            &BindingForm::Associative {token, ref entries, ref defaults, whole, meta} => {
+                let map = self.new_gensym(token, "map");
+                match meta {
+                    Some(meta) => map.set_meta(meta),
+                    None => {}
+                }
+                self.bind_symbol(let_form, scope, map, value);
+                match whole {
+                    Some(sym) => self.bind_symbol(let_form, scope, sym, self.new_reference(token, map)),
+                    None => {}
+                }
+                for &(ref entry, key) in entries.iter() {
+                    let mut args = vec![self.new_reference(token, map), key];
+                    match entry {
+                        &BindingForm::Symbol(sym) => {
+                            let name = sym.token().unwrap().value();
+                            match defaults.iter().find(|&&(s, _)| s.token().unwrap().value() == name) {
+                                Some(&(_, default)) => args.push(default),
+                                None => {}
+                            }
+                        }
+                        _ => {}
+                    }
+                    let get = self.new_call(token, "get", args);
+                    self.bind_form(let_form, scope, entry, get);
+                }
+            }
+        }
+    }
+
+
+    fn bind_symbol(&self, let_form: &'a Ast<'a>, scope: &'a Scope<'a>, sym: &'a Ast<'a>, value: &'a Ast<'a>) {
+        match sym.symbol_mode() {
+            SymbolMode::Unresolved => {
+                sym.set_symbol_mode(SymbolMode::Var(SymbolDepth::Origin));
+            }
+            _ => {}
+        }
+        scope.intern(sym);
+        sym.bind_to_symbol(value);
+        let_form.add_let_binding((sym, value));
+    }
+
+
+    /// Create a symbol `prefix__N` which never conflicts with the symbols in the source.
+    fn new_gensym(&self, token: Token<'a>, prefix: &str) -> &'a Ast<'a> {
+        let id = self.gensym_id.get() + 1;
+        self.gensym_id.set(id);
+        self.new_builtin_symbol(token, &format!("{}__{}", prefix, id))
+    }
+
+
+    /// The symbol referring to the binding `sym` from the current scope.
+    fn new_reference(&self, token: Token<'a>, sym: &'a Ast<'a>) -> &'a Ast<'a> {
+        let id = sym.token().unwrap().value();
+        self.process_sym(Token::new_value(token.info(), id, TokenKind::Symbol)).ok().unwrap()
+    }
+
+
+    fn parse_if(&self, if_form: &'a Ast<'a>) -> ParseResult<'a> {
+        let mut token = next_token!(self);
+        match self.do_parse_form(if_form, token, |ast: &'a Ast<'a>| if_form.set_cond(ast)) {
+            Err(e) => {return Err(e);}
+            _ => {}
+        }
+
+        token = next_token!(self);
+        match self.do_parse_form(if_form, token, |ast: &'a Ast<'a>| if_form.set_then_body(ast)) {
+            Err(e) => {return Err(e);}
+            _ => {}
+        }
+
+        let peeked = match self.peek_token() {
+            Ok(token) => token,
+            Err(e) => {return Err(e);}
+        };
+        if !peeked.is(TokenKind::RightParen) {
+            token = next_token!(self);
+            match self.do_parse_form(if_form, token, |ast: &'a Ast<'a>| if_form.set_else_body(ast)) {
+                Err(e) => {return Err(e);},
+                _ => {}
+            }
+        }
+
+        token = next_token!(self);
+
+        if token.kind() == TokenKind::RightParen {
+            return self.close(if_form, token);
+        }
+
+        Err(ParseError::new("')' expected.", token))
+    }
+
+
+    /// `(quote x)` is read into the same list as `'x`.
+    fn parse_quote(&self, token: Token<'a>, head: Token<'a>) -> ParseResult<'a> {
+        let quote = Ast::new_list(self.zone_allocator, token);
+        quote.add_child(self.new_builtin_symbol(head, "quote"));
+        let mut token = next_token!(self);
+        match self.read_datum(token) {
+            Ok(ast) => quote.add_child(ast),
+            Err(e) => {return Err(e)}
+        }
+
+        token = next_token!(self);
+
+        if token.kind() == TokenKind::RightParen {
+            return self.close(quote, token);
+        }
+
+        Err(ParseError::new("quote expected only one argument.", token))
+    }
+
+
+    fn do_parse_form<T>(&self, form: &'a Ast<'a>, token: Token<'a>, mut add: T) -> ParseResult<'a> where
+        T: FnMut(&'a Ast<'a>) {
+        let next = token;
+        check_token!(self, form, next, {
+            match next.kind() {
+                TokenKind::LeftParen => {
+                    match self.parse_form(next) {
+                        Ok(ast) => add(ast),
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+                TokenKind::ShortLambdaBegin => {
+                    match self.parse_short_lambda(next) {
+                        Ok(ast) => add(ast),
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+                TokenKind::SetBegin => {
+                    match self.parse_set(next) {
+                        Ok(ast) => add(ast),
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+                TokenKind::VarQuote => {
+                    match self.parse_var_quote(next) {
+                        Ok(ast) => add(ast),
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+                TokenKind::ReaderConditional |
+                TokenKind::ReaderConditionalSplicing => {
+                    match self.parse_reader_conditional(next) {
+                        Ok(Some(ast)) => {
+                            if next.is(TokenKind::ReaderConditional) {
+                                add(ast);
+                            } else {
+                                match ast {
+                                    &Ast::List(_) | &Ast::Vector(_) => {
+                                        for child in ast.children().unwrap().iter() {
+                                            add(*child);
+                                        }
+                                    }
+                                    _ => {
+                                        return Result::Err(ParseError::new("#?@ expected a list or a vector to splice.", next));
+                                    }
+                                }
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+                TokenKind::LeftBracket => {
+                    match self.parse_vector(next) {
+                        Ok(ast) => add(ast),
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+                TokenKind::LeftBrace => {
+                    match self.parse_map(next) {
+                        Ok(ast) => add(ast),
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+                TokenKind::Tag => {
+                    match self.parse_tag(next) {
+                        Ok(ast) => add(ast),
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+                TokenKind::TaggedLiteral => {
+                    match self.parse_tagged_literal(next) {
+                        Ok(ast) => add(ast),
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+                TokenKind::Deref => {
+                    match self.parse_deref(next) {
+                        Ok(ast) => add(ast),
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+                TokenKind::QuoteRm => {
+                    match self.parse_quote_rm(next) {
+                        Ok(ast) => add(ast),
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+                TokenKind::Backtick => {
+                    match self.parse_syntax_quote(next) {
+                        Ok(ast) => add(ast),
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+                TokenKind::Unquote |
+                TokenKind::UnquoteSplicing => {
+                    return Result::Err(ParseError::new("Unquote used outside of syntax-quote.", next));
+                }
+                _ => {
+                    match self.parse_literal(next) {
+                        Ok(ast) => add(ast),
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+            }
+        });
+        Result::Ok(form)
+    }
+
+
+    fn parse_vector(&self, token: Token<'a>) -> ParseResult<'a> {
+        let depth = self.delimiters.borrow().len();
+        let vector = Ast::new_vector(self.zone_allocator, token);
+        loop {
+            let next = next_token!(self);
+            check_token!(self, vector, next, {
+                match next.kind() {
+                    TokenKind::RightBracket => {
+                        return self.close(vector, next);
+                    }
+                    _ => {
+                        match self.parse_element(vector, depth, next) {
+                            Ok(false) => {},
+                            Ok(true) => {return Result::Ok(vector);}
+                            Err(e) => {return Result::Err(e);}
+                        }
+                    }
+                }
+            })
+        }
+    }
+
+
+    /// Parse an element of the list or the vector which is opened at `depth`.
+    /// A broken element is replaced with Ast::Error and the rest of the elements are parsed,
+    /// only the end of input is returned as the error since nothing is left to recover.
+    /// Returns true if a mismatched delimiter in the element closed the sequence too.
+    fn parse_element(&self, seq: &'a Ast<'a>, depth: usize, token: Token<'a>) -> Result<bool, ParseError<'a>> {
+        match self.do_parse_form(seq, token, |ast: &'a Ast<'a>| seq.add_child(ast)) {
+            Ok(_) => {},
+            Err(e) => {
+                if e.is_incomplete() {
+                    return Result::Err(e);
+                }
+                seq.add_child(self.recover(depth, token, e));
+            }
+        }
+        Result::Ok(self.delimiters.borrow().len() < depth)
+    }
+
+
+    fn parse_set(&self, token: Token<'a>) -> ParseResult<'a> {
+        let set = Ast::new_set(self.zone_allocator, token);
+        loop {
+            let next = next_token!(self);
+            check_token!(self, set, next, {
+                match next.kind() {
+                    TokenKind::RightBrace => {
+                        return self.close(set, next);
+                    }
+                    _ => {
+                        let mut duplicated = None;
+                        match self.do_parse_form(set, next, |ast: &'a Ast<'a>| {
+                            if set.children().unwrap().iter().any(|child| self.is_same_form(child, ast)) {
+                                duplicated = ast.token();
+                            }
+                            set.add_child(ast);
+                        }) {
+                            Ok(ast) => {},
+                            Err(e) => {return Result::Err(e);}
+                        }
+                        match duplicated {
+                            Some(token) => {
+                                return Result::Err(ParseError::new("Duplicate set element.", token));
+                            }
+                            None => {}
+                        }
+                    }
+                }
+            })
+        }
+    }
+
+
+    /// Read #'sym as (var sym).
+    fn parse_var_quote(&self, token: Token<'a>) -> ParseResult<'a> {
+        let next = next_token!(self);
+        check_token!(self, token, next, {
+            if !next.is(TokenKind::Symbol) {
+                return Result::Err(ParseError::new("#' expected a symbol.", next));
+            }
+        });
+        match self.resolve_sym(next, true) {
+            Ok(ast) => Result::Ok(self.desugar(token, "var", ast)),
+            Err(e) => Result::Err(e)
+        }
+    }
+
+
+    /// `@x` is read into `(deref x)`.
+    fn parse_deref(&self, token: Token<'a>) -> ParseResult<'a> {
+        match self.read_form(token) {
+            Ok(ast) => Result::Ok(self.desugar(token, "deref", ast)),
+            Err(e) => Result::Err(e)
+        }
+    }
+
+
+    /// Read `#tag form` and replace it by the result of the reader registered for the tag.
+    fn parse_tagged_literal(&self, token: Token<'a>) -> ParseResult<'a> {
+        let tag = get_token_value!(self, token);
+        let reader = match self.tag_readers.borrow().get(tag) {
+            Some(reader) => reader.clone(),
+            None => {
+                return Result::Err(ParseError::new(&format!("No reader function for tag {}.", tag), token));
+            }
+        };
+        let form = match self.read_form(token) {
+            Ok(ast) => ast,
+            Err(e) => {return Result::Err(e);}
+        };
+        match reader(self, form) {
+            Ok(ast) => Result::Ok(ast),
+            Err(message) => Result::Err(ParseError::new(&message, token))
+        }
+    }
+
+
+    /// `#inst "1985-04-12T23:20:50.52Z"` is read into `(risp.core/inst "...")`
+    /// after checking that the string is a RFC3339 timestamp.
+    /// As in Clojure, the trailing parts of the timestamp may be omitted.
+    fn read_inst(&self, re: &Regex, form: &'a Ast<'a>) -> Result<&'a Ast<'a>, std::string::String> {
+        let value = match form {
+            &Ast::String(_) => form.string_value().unwrap(),
+            _ => {return Err("#inst expected a string.".to_string());}
+        };
+        let caps = match re.captures(value) {
+            Some(caps) => caps,
+            None => {return Err(format!("Invalid timestamp {}.", value));}
+        };
+        let field = |i: usize, default: u32| caps.at(i).map(|v| v.parse::<u32>().unwrap()).unwrap_or(default);
+        let (year, month, day) = (field(1, 0), field(2, 1), field(3, 1));
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days = match month {
+            2 => if leap {29} else {28},
+            4 | 6 | 9 | 11 => 30,
+            _ => 31
+        };
+        if month < 1 || month > 12 || day < 1 || day > days || field(4, 0) > 23 || field(5, 0) > 59 ||
+            field(6, 0) > 60 || field(7, 0) > 23 || field(8, 0) > 59 {
+            return Err(format!("Invalid timestamp {}.", value));
+        }
+        Ok(self.new_call(form.token().unwrap(), "inst", vec![form]))
+    }
+
+
+    /// `#uuid "f81d4fae-7dec-11d0-a765-00a0c91e6bf6"` is read into `(risp.core/uuid "...")`.
+    fn read_uuid(&self, re: &Regex, form: &'a Ast<'a>) -> Result<&'a Ast<'a>, std::string::String> {
+        let value = match form {
+            &Ast::String(_) => form.string_value().unwrap(),
+            _ => {return Err("#uuid expected a string.".to_string());}
+        };
+        if !re.is_match(value) {
+            return Err(format!("Invalid uuid {}.", value));
+        }
+        Ok(self.new_call(form.token().unwrap(), "uuid", vec![form]))
+    }
+
+
+    /// `'x` is read into `(quote x)`, the quoted form is read as data.
+    fn parse_quote_rm(&self, token: Token<'a>) -> ParseResult<'a> {
+        let next = next_token!(self);
+        match self.read_datum(next) {
+            Ok(ast) => Result::Ok(self.desugar(token, "quote", ast)),
+            Err(e) => Result::Err(e)
+        }
+    }
+
+
+    /// The canonical list form `(name form)` of the reader macro `token`,
+    /// so that later passes never see the reader macros themselves.
+    fn desugar(&self, token: Token<'a>, name: &str, form: &'a Ast<'a>) -> &'a Ast<'a> {
+        let list = Ast::new_list(self.zone_allocator, token);
+        list.add_child(self.new_builtin_symbol(token, name));
+        list.add_child(form);
+        self.extend_to(list, form);
+        list
+    }
+
+
+    /// Read #?(feature form ...) and return the form of the first feature
+    /// which matches :risp or :default, None if nothing matches.
+    fn parse_reader_conditional(&self, token: Token<'a>) -> Result<Option<&'a Ast<'a>>, ParseError<'a>> {
+        let next = next_token!(self);
+        if !next.is(TokenKind::LeftParen) {
+            return Result::Err(ParseError::new("Reader conditional body must be a list.", next));
+        }
+
+        let clauses = Ast::new_list(self.zone_allocator, next);
+        loop {
+            let next = next_token!(self);
+            match next.kind() {
+                TokenKind::RightParen => break,
+                TokenKind::Eof => {
+                    return Result::Err(ParseError::new_incomplete("Unexpected end of input", next));
+                }
+                _ => {
+                    match self.do_parse_form(clauses, next, |ast: &'a Ast<'a>| clauses.add_child(ast)) {
+                        Ok(ast) => {},
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+            }
+        }
+
+        let children = clauses.children().unwrap();
+        if children.len() % 2 != 0 {
+            return Result::Err(ParseError::new("Reader conditional requires an even number of forms.", token));
+        }
+
+        for pair in children.chunks(2) {
+            match pair[0] {
+                &Ast::Keyword(_) => {
+                    match pair[0].string_value() {
+                        Some(":risp") | Some(":default") => {
+                            return Result::Ok(Some(pair[1]));
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {
+                    return Result::Err(ParseError::new("Reader conditional feature must be a keyword.", pair[0].token().unwrap()));
+                }
+            }
+        }
+        Result::Ok(None)
+    }
+
+
+    fn parse_map(&self, token: Token<'a>) -> ParseResult<'a> {
+        let map = Ast::new_map(self.zone_allocator, token);
+        loop {
+            let next = next_token!(self);
+            check_token!(self, map, next, {
+                match next.kind() {
+                    TokenKind::RightBrace => {
+                        match map.children() {
+                            Some(children) => {
+                                if children.len() % 2 != 0 {
+                                    match map.token() {
+                                        Some(token) => {
+                                            return Result::Err(ParseError::new("map expected key-value pair.", token));
+                                        }
+                                        _ => {}
+                                    }
+                                }
+                                let keys: Vec<&'a Ast<'a>> = children.iter().step_by(2).map(|k| *k).collect();
+                                for (i, key) in keys.iter().enumerate() {
+                                    if keys[..i].iter().any(|k| self.is_same_form(k, key)) {
+                                        return Result::Err(ParseError::new("Duplicate map key.", key.token().unwrap_or(next)));
+                                    }
+                                }
+                            },
+                            _ => {}
+                        }
+                        return self.close(map, next);
+                    }
+                    _ => {
+                        match self.do_parse_form(map, next, |ast: &'a Ast<'a>| map.add_child(ast)) {
+                            Ok(ast) => {},
+                            Err(e) => {return Result::Err(e);}
+                        }
+                    }
+                }
+            })
+        }
+    }
+
+
+    /// Read `^meta form` and merge the metadata into the metadata map of the form,
+    /// the form itself is returned. `^:kw` is a shorthand of `^{:kw true}`
+    /// and `^Type` or `^"Type"` of `^{:tag Type}`.
+    /// When stacked, the outer metadata wins, `^:a ^{:a false} x` has `:a true`.
+    fn parse_tag(&self, token: Token<'a>) -> ParseResult<'a> {
+        let entries = match self.read_meta(token) {
+            Ok(entries) => entries,
+            Err(e) => {return Result::Err(e);}
+        };
+        let form = match self.read_form(token) {
+            Ok(ast) => ast,
+            Err(e) => {return Result::Err(e);}
+        };
+        match form {
+            &Ast::Module(_) => {}
+            _ => form.set_meta(self.add_meta(form.meta(), entries))
+        }
+        Result::Ok(form)
+    }
+
+
+    /// Read the metadata following `^` into the metadata map.
+    fn read_meta(&self, token: Token<'a>) -> ParseResult<'a> {
+        let meta = match self.read_form(token) {
+            Ok(ast) => ast,
+            Err(e) => {return Result::Err(e);}
+        };
+        let entries = Ast::new_map(self.zone_allocator, token);
+        match meta {
+            &Ast::Map(_) => {
+                for child in meta.children().unwrap().iter() {
+                    entries.add_child(*child);
+                }
+            }
+            &Ast::Keyword(ref k) => {
+                entries.add_child(meta);
+                entries.add_child(Ast::new_boolean(self.zone_allocator, k.token(), true));
+            }
+            &Ast::Symbol(_) | &Ast::String(_) => {
+                entries.add_child(self.new_builtin_keyword(token, ":tag"));
+                entries.add_child(meta);
+            }
+            _ => {
+                return Result::Err(ParseError::new("Metadata must be a symbol, keyword, string or map.", token));
+            }
+        }
+        Result::Ok(entries)
+    }
+
+
+    /// The metadata map of `entries` added outside of the metadata `inner`.
+    fn add_meta(&self, inner: Option<&'a Ast<'a>>, entries: &'a Ast<'a>) -> &'a Ast<'a> {
+        match inner {
+            Some(inner) => self.merge_meta(inner, entries),
+            None => entries
+        }
+    }
+
+
+    /// Merge the metadata maps, the entries of `outer` override the ones of `inner`.
+    fn merge_meta(&self, inner: &'a Ast<'a>, outer: &'a Ast<'a>) -> &'a Ast<'a> {
+        let merged = Ast::new_map(self.zone_allocator, inner.token().unwrap());
+        let outer_entries = outer.children().unwrap();
+        for pair in inner.children().unwrap().chunks(2) {
+            if !outer_entries.chunks(2).any(|o| self.is_same_form(o[0], pair[0])) {
+                merged.add_child(pair[0]);
+                merged.add_child(pair[1]);
+            }
+        }
+        for child in outer_entries.iter() {
+            merged.add_child(*child);
+        }
+        merged
+    }
+
+
+    /// Read `` `form `` and expand it into the code which constructs the form.
+    /// Symbols are qualified with the namespace, `x#` is replaced by a symbol
+    /// unique to this syntax-quote, `~x` is evaluated and `~@xs` is spliced.
+    fn parse_syntax_quote(&self, token: Token<'a>) -> ParseResult<'a> {
+        let next = next_token!(self);
+        self.gensyms.borrow_mut().push(HashMap::new());
+        let ret = match self.read_datum(next) {
+            Ok(datum) => self.syntax_quote(token, datum),
+            Err(e) => Err(e)
+        };
+        self.gensyms.borrow_mut().pop();
+        ret
+    }
+
+
+    /// Read a form as data, special forms are not recognized and symbols are not resolved.
+    /// Unquoted forms are parsed as code and marked as `(unquote x)` or `(unquote-splicing x)`.
+    fn read_datum(&self, token: Token<'a>) -> ParseResult<'a> {
+        match token.kind() {
+            TokenKind::LeftParen => self.read_datum_seq(Ast::new_list(self.zone_allocator, token), TokenKind::RightParen),
+            TokenKind::LeftBracket => self.read_datum_seq(Ast::new_vector(self.zone_allocator, token), TokenKind::RightBracket),
+            TokenKind::LeftBrace => self.read_datum_seq(Ast::new_map(self.zone_allocator, token), TokenKind::RightBrace),
+            TokenKind::SetBegin => self.read_datum_seq(Ast::new_set(self.zone_allocator, token), TokenKind::RightBrace),
+            TokenKind::Unquote |
+            TokenKind::UnquoteSplicing => {
+                let name = if token.is(TokenKind::Unquote) {"unquote"} else {"unquote-splicing"};
+                match self.read_form(token) {
+                    Ok(ast) => Result::Ok(self.desugar(token, name, ast)),
+                    Err(e) => Result::Err(e)
+                }
+            }
+            TokenKind::QuoteRm => self.parse_quote_rm(token),
+            TokenKind::Symbol => {
+                Result::Ok(Ast::new_symbol(self.zone_allocator, token, get_token_value!(self, token), SymbolMode::Unresolved))
+            }
+            TokenKind::ParamName => {
+                match self.short_lambda_param(token) {
+                    Ok(Some(param)) => Result::Ok(self.new_builtin_symbol(token, get_token_value!(self, param.token().unwrap()))),
+                    Ok(None) => Result::Ok(self.new_builtin_symbol(token, get_token_value!(self, token))),
+                    Err(e) => Result::Err(e)
+                }
+            }
+            TokenKind::ShortLambdaBegin => self.read_datum_short_lambda(token),
+            _ => self.parse_single_form(token)
+        }
+    }
+
+
+    fn read_datum_seq(&self, seq: &'a Ast<'a>, close: TokenKind) -> ParseResult<'a> {
+        loop {
+            let next = next_token!(self);
+            check_token!(self, seq, next, {
+                if next.is(close) {
+                    return self.close(seq, next);
+                }
+                match self.read_datum(next) {
+                    Ok(ast) => seq.add_child(ast),
+                    Err(e) => {return Result::Err(e);}
+                }
+            })
+        }
+    }
+
+
+    /// The code which constructs `datum`.
+    fn syntax_quote(&self, token: Token<'a>, datum: &'a Ast<'a>) -> ParseResult<'a> {
+        match self.unquoted(datum) {
+            Some((false, form)) => {return Result::Ok(form);}
+            Some((true, _)) => {
+                return Result::Err(ParseError::new("Unquote-splicing used outside of a list.", datum.token().unwrap()));
+            }
+            None => {}
+        }
+
+        match datum {
+            &Ast::List(_) => {
+                if datum.children().unwrap().is_empty() {
+                    return Result::Ok(self.new_call(token, "list", vec![]));
+                }
+                self.syntax_quote_seq(token, datum)
+            }
+            &Ast::Vector(_) |
+            &Ast::Map(_) |
+            &Ast::Set(_) => {
+                let constructor = match datum {
+                    &Ast::Vector(_) => "vector",
+                    &Ast::Map(_) => "hash-map",
+                    _ => "hash-set"
+                };
+                match self.syntax_quote_seq(token, datum) {
+                    Ok(seq) => Result::Ok(self.new_call(token, "apply", vec![self.new_core_symbol(token, constructor), seq])),
+                    Err(e) => Result::Err(e)
+                }
+            }
+            &Ast::Symbol(ref s) => Result::Ok(self.desugar(token, "quote", self.qualify(s.token()))),
+            &Ast::ModuleReference(_) => Result::Ok(self.desugar(token, "quote", datum)),
+            &Ast::Keyword(_) |
+            &Ast::String(_) |
+            &Ast::UChar(_) |
+            &Ast::Integer(_) |
+            &Ast::Long(_) |
+            &Ast::BigInteger(_) |
+            &Ast::BigDecimal(_) |
+            &Ast::Ratio(_) |
+            &Ast::Double(_) |
+            &Ast::Boolean(_) |
+            &Ast::RegExp(_) |
+            &Ast::Nil(_) => Result::Ok(datum),
+            _ => Result::Err(ParseError::new("Unsupported form in syntax-quote.", datum.token().unwrap_or(token)))
+        }
+    }
+
+
+    /// `(seq (concat ...))` of the elements of `datum`, the unquote-spliced elements are concatenated as they are.
+    fn syntax_quote_seq(&self, token: Token<'a>, datum: &'a Ast<'a>) -> ParseResult<'a> {
+        let mut pieces = Vec::new();
+        for child in datum.children().unwrap().iter() {
+            match self.unquoted(child) {
+                Some((true, form)) => pieces.push(form),
+                _ => {
+                    match self.syntax_quote(token, child) {
+                        Ok(ast) => pieces.push(self.new_call(token, "list", vec![ast])),
+                        Err(e) => {return Result::Err(e);}
+                    }
+                }
+            }
+        }
+        let concat = self.new_call(token, "concat", pieces);
+        Result::Ok(self.new_call(token, "seq", vec![concat]))
+    }
+
+
+    /// The form of the `(unquote x)` or `(unquote-splicing x)` marker, with true if spliced.
+    fn unquoted(&self, datum: &'a Ast<'a>) -> Option<(bool, &'a Ast<'a>)> {
+        match datum {
+            &Ast::List(_) => {
+                let children = datum.children().unwrap();
+                if children.len() != 2 {
+                    return None;
+                }
+                match children[0].symbol_value() {
+                    Some("unquote") => Some((false, children[1])),
+                    Some("unquote-splicing") => Some((true, children[1])),
+                    _ => None
+                }
+            }
+            _ => None
+        }
+    }
+
+
+    /// Qualify the symbol in syntax-quote with the namespace,
+    /// or replace `x#` by the auto-gensym of the current syntax-quote.
+    fn qualify(&self, token: Token<'a>) -> &'a Ast<'a> {
+        let name = get_token_value!(self, token);
+        if name.len() > 1 && name.ends_with('#') {
+            let mut gensyms = self.gensyms.borrow_mut();
+            let current = gensyms.last_mut().unwrap();
+            if !current.contains_key(&token.value()) {
+                self.gensym_id.set(self.gensym_id.get() + 1);
+                let generated = format!("{}__{}__auto__", &name[..name.len() - 1], self.gensym_id.get());
+                current.insert(token.value(), self.literal_buffer.find(self.literal_buffer.get(&generated)));
+            }
+            return self.new_builtin_symbol(token, current[&token.value()]);
+        }
+
+        if name.contains('/') || name.contains('.') || name == "&" ||
+            self.builtin_token_registry.special_form(token.value()).is_some() {
+            return match self.process_sym(token) {
+                Ok(sym) => sym,
+                Err(_) => {
+                    let slash = name.find('/').unwrap();
+                    self.new_module_reference(token, &name[..slash], &name[slash + 1..])
+                }
+            };
+        }
+        let qualified = format!("{}/{}", self.namespace.get(), name);
+        self.new_qualified_symbol(token, &qualified)
+    }
+
+
+    /// `(risp.core/name args...)`
+    fn new_call(&self, token: Token<'a>, name: &str, args: Vec<&'a Ast<'a>>) -> &'a Ast<'a> {
+        let call = Ast::new_list(self.zone_allocator, token);
+        call.add_child(self.new_core_symbol(token, name));
+        for arg in args {
+            call.add_child(arg);
+        }
+        call
+    }
+
+
+    fn new_core_symbol(&self, token: Token<'a>, name: &str) -> &'a Ast<'a> {
+        self.new_qualified_symbol(token, &format!("{}/{}", CORE_NAMESPACE, name))
+    }
+
+
+    fn new_qualified_symbol(&self, token: Token<'a>, name: &str) -> &'a Ast<'a> {
+        let id = self.literal_buffer.get(name);
+        self.process_sym(Token::new_value(token.info(), id, TokenKind::Symbol)).ok().unwrap()
+    }
+
+
+    /// Read the single form following the reader macro `token`.
+    fn read_form(&self, token: Token<'a>) -> ParseResult<'a> {
+        let next = next_token!(self);
+        self.parse_single_form(next)
+    }
+
+
+    /// Parse the single form beginning with `next`.
+    fn parse_single_form(&self, next: Token<'a>) -> ParseResult<'a> {
+        let holder = Ast::new_list(self.zone_allocator, next);
+        match self.do_parse_form(holder, next, |ast: &'a Ast<'a>| holder.add_child(ast)) {
+            Ok(_) => {}
+            Err(e) => {return Result::Err(e);}
+        }
+        let children = holder.children().unwrap();
+        match children.len() {
+            0 => Result::Err(ParseError::new("Form expected.", next)),
+            1 => Result::Ok(children[0]),
+            _ => Result::Err(ParseError::new("Single form expected, but #?@ spliced multiple forms.", next))
+        }
+    }
+
+
+    /// Read the name bound by let, def or defmacro, which may have metadata.
+    /// The name is resolved in the scope but not by `:refer`, since it is being defined.
+    fn parse_name(&self, token: Token<'a>) -> ParseResult<'a> {
+        check_token!(self, token, token, {});
+        if token.is(TokenKind::Tag) {
+            return self.parse_tag(token);
+        }
+        if token.is(TokenKind::Symbol) && !get_token_value!(self, token).contains('/') {
+            return self.resolve_unqualified_sym(token, false);
+        }
+        self.parse_literal(token)
+    }
+
+
+    /// Read #(f % %2 %&) as (fn [p1 p2 & rest] (f p1 p2 rest)).
+    /// The arity is the highest %n, % is %1 and %& is the rest parameter.
+    fn parse_short_lambda(&self, token: Token<'a>) -> ParseResult<'a> {
+        if self.short_lambda.borrow().is_some() {
+            return Result::Err(ParseError::new("Nested #()s are not allowed.", token));
+        }
+        return self.scope_handler.enter(|scope: &'a Scope<'a>| -> ParseResult<'a> {
+            let id = self.gensym_id.get() + 1;
+            self.gensym_id.set(id);
+            *self.short_lambda.borrow_mut() = Some(ShortLambda {
+                id: id,
+                token: token,
+                scope: scope,
+                params: Vec::new(),
+                rest: None,
+                rest_refs: Vec::new()
+            });
+            let ret = self.parse_form(token);
+            let params = self.short_lambda.borrow_mut().take().unwrap();
+            let body = match ret {
+                Ok(body) => body,
+                Err(e) => {return Result::Err(e);}
+            };
+
+            let arity = params.params.len() as i32;
+            let lambda = Ast::new_lambda(self.zone_allocator, token, scope);
+            scope.set_origin(lambda);
+            for param in params.params.iter() {
+                lambda.add_lambda_arg(param);
+            }
+            match params.rest {
+                Some(rest) => {
+                    for sym in params.rest_refs.iter().chain(Some(rest).iter()) {
+                        match sym.symbol_mode() {
+                            SymbolMode::RestParameter{index, depth} => {
+                                sym.set_symbol_mode(SymbolMode::RestParameter{index: arity, depth: depth});
+                            }
+                            _ => {}
+                        }
+                    }
+                    lambda.set_rest_parameter(rest);
+                }
+                None => {}
+            }
+            lambda.add_lambda_body(body);
+            lambda.extend_source_info(body.token().unwrap().info());
+
+            let sugar = Ast::new_lambda_sugar(self.zone_allocator, body.token().unwrap());
+            sugar.set_lambda_sugar_arity(arity, params.rest.is_some());
+            sugar.add_child(lambda);
+            Result::Ok(sugar)
+        });
+    }
+
+
+    /// Read #(f % %&) as data, which is the list `(fn [p1__N# & rest__N#] (f p1__N# rest__N#))`
+    /// so that syntax-quote replaces the parameters with unique symbols.
+    fn read_datum_short_lambda(&self, token: Token<'a>) -> ParseResult<'a> {
+        if self.short_lambda.borrow().is_some() {
+            return Result::Err(ParseError::new("Nested #()s are not allowed.", token));
+        }
+        return self.scope_handler.enter(|scope: &'a Scope<'a>| -> ParseResult<'a> {
+            let id = self.gensym_id.get() + 1;
+            self.gensym_id.set(id);
+            *self.short_lambda.borrow_mut() = Some(ShortLambda {
+                id: id,
+                token: token,
+                scope: scope,
+                params: Vec::new(),
+                rest: None,
+                rest_refs: Vec::new()
+            });
+            let ret = self.read_datum_seq(Ast::new_list(self.zone_allocator, token), TokenKind::RightParen);
+            let params = self.short_lambda.borrow_mut().take().unwrap();
+            let body = match ret {
+                Ok(body) => body,
+                Err(e) => {return Result::Err(e);}
+            };
+
+            let args = Ast::new_vector(self.zone_allocator, token);
+            for param in params.params.iter() {
+                args.add_child(self.new_builtin_symbol(token, get_token_value!(self, param.token().unwrap())));
+            }
+            match params.rest {
+                Some(rest) => {
+                    args.add_child(self.new_builtin_symbol(token, "&"));
+                    args.add_child(self.new_builtin_symbol(token, get_token_value!(self, rest.token().unwrap())));
+                }
+                None => {}
+            }
+            let lambda = Ast::new_list(self.zone_allocator, token);
+            lambda.add_child(self.new_builtin_symbol(token, "fn"));
+            lambda.add_child(args);
+            lambda.add_child(body);
+            Result::Ok(lambda)
+        });
+    }
+
+
+    /// Resolve %, %n or %& to the generated parameter of the enclosing #(...),
+    /// outside of #() they are plain symbols.
+    fn parse_param_name(&self, token: Token<'a>) -> ParseResult<'a> {
+        let param = match self.short_lambda_param(token) {
+            Ok(Some(param)) => param,
+            Ok(None) => {return self.process_sym(Token::new_value(token.info(), token.value(), TokenKind::Symbol));}
+            Err(e) => {return Result::Err(e);}
+        };
+        let reference = Token::new_value(token.info(), param.token().unwrap().value(), TokenKind::Symbol);
+        let sym = match self.process_sym(reference) {
+            Ok(sym) => sym,
+            Err(e) => {return Result::Err(e);}
+        };
+        if get_token_value!(self, token) == "%&" {
+            self.short_lambda.borrow_mut().as_mut().unwrap().rest_refs.push(sym);
+        }
+        Result::Ok(sym)
+    }
+
+
+    /// The generated parameter of the enclosing #(...) which `token` refers,
+    /// None if `token` is not in #().
+    fn short_lambda_param(&self, token: Token<'a>) -> Result<Option<&'a Ast<'a>>, ParseError<'a>> {
+        let name = get_token_value!(self, token);
+        let mut state = self.short_lambda.borrow_mut();
+        let lambda = match state.as_mut() {
+            Some(lambda) => lambda,
+            None => {return Result::Ok(None);}
+        };
+        if name == "%&" {
+            return Result::Ok(Some(match lambda.rest {
+                Some(rest) => rest,
+                None => {
+                    let rest = self.new_param(lambda, &format!("rest__{}#", lambda.id),
+                                              SymbolMode::RestParameter{index: 0, depth: SymbolDepth::Origin});
+                    lambda.rest = Some(rest);
+                    rest
+                }
+            }));
+        }
+        let index = if name == "%" {
+            1
+        } else {
+            match name[1..].parse::<usize>() {
+                Ok(i) if i > 0 => i,
+                _ => {return Result::Err(ParseError::new("Invalid lambda parameter.", token));}
+            }
+        };
+        while lambda.params.len() < index {
+            let n = lambda.params.len() + 1;
+            let param = self.new_param(lambda, &format!("p{}__{}#", n, lambda.id),
+                                       SymbolMode::Parameter{index: n as i32 - 1, depth: SymbolDepth::Origin});
+            lambda.params.push(param);
+        }
+        Result::Ok(Some(lambda.params[index - 1]))
+    }
+
+
+    /// Create a parameter of #(...) and bind it in the scope of the lambda,
+    /// the index of the rest parameter is fixed when the arity is known.
+    fn new_param(&self, lambda: &ShortLambda<'a>, name: &str, mode: SymbolMode) -> &'a Ast<'a> {
+        let param = self.new_builtin_symbol(lambda.token, name);
+        param.set_symbol_mode(mode);
+        lambda.scope.intern(param);
+        param
+    }
+
+
+    fn parse_literal(&self, token: Token<'a>) -> ParseResult<'a> {
+        match token.kind() {
+            TokenKind::Keyword => {
+                Ok(Ast::new_keyword(self.zone_allocator, token, get_token_value!(self, token)))
+            }
+            TokenKind::MacroKeyword => {
+                self.parse_auto_resolved_keyword(token)
+            }
+            TokenKind::Hex|
+            TokenKind::Binary|
+            TokenKind::Radix|
+            TokenKind::Int|
+            TokenKind::Long|
+            TokenKind::BigNumber|
+            TokenKind::BigDecimal|
+            TokenKind::Ratio => {
+                match self.parse_number(token) {
+                    Ok(ast) => Ok(ast),
+                    Err(e) => Err(ParseError::new(e, token))
+                }
+            }
+            TokenKind::Float => {
+                match get_token_value!(self, token).parse::<f64>() {
+                    Ok(i) => Ok(Ast::new_double(self.zone_allocator, token, i)),
+                    Err(_) => {
+                        return Result::Err(ParseError::new("Invalid double value.", token));
+                    }
+                }
+            }
+            TokenKind::String => {
+                let raw = get_token_value!(self, token);
+                match self.unescape_string(raw, token) {
+                    Ok(value) => {
+                        let id = self.literal_buffer.get(&value);
+                        Ok(Ast::new_string(self.zone_allocator, token, self.literal_buffer.find(id), raw))
+                    }
+                    Err(e) => Err(e)
+                }
+            }
+            TokenKind::Regexp => {
+                let literal = get_token_value!(self, token);
+                let pattern = &literal[2..literal.len() - 1];
+                match Regex::new(pattern) {
+                    Ok(regex) => Ok(Ast::new_regexp(self.zone_allocator, token, pattern, regex)),
+                    Err(e) => Err(self.regex_error(token, pattern, e))
+                }
+            }
+            TokenKind::Symbol => {
+                return self.process_sym(token);
+            }
+            TokenKind::Boolean => {
+                if get_token_value!(self, token) == "true" {
+                    return Ok(Ast::new_boolean(self.zone_allocator, token, true));
+                }
+                return Ok(Ast::new_boolean(self.zone_allocator, token, false));
+            }
+            TokenKind::UnicodeChar => {
+                let v = get_token_value!(self, token);
+                match self.parse_char_literal(v) {
+                    Ok(i) => Ok(Ast::new_uchar(self.zone_allocator, token, i)),
+                    Err(e) => Err(ParseError::new(e, token))
+                }
+            }
+            TokenKind::Nil => {
+                Ok(Ast::new_nil(self.zone_allocator, token))
+            }
+            TokenKind::ParamName => {
+                self.parse_param_name(token)
+            }
+            _ => {
+                Err(ParseError::new("Invalid Token.", token))
+            }
+        }
+    }
+
+
+    fn process_sym(&self, token: Token<'a>) -> ParseResult<'a> {
+        self.resolve_sym(token, false)
+    }
+
+
+    /// Resolve the symbol to the binding in the scope, or `ns/name` to the var of
+    /// the namespace which `ns` stands for. `private_access` allows the private
+    /// vars of the other modules, which #'ns/name can refer to.
+    fn resolve_sym(&self, token: Token<'a>, private_access: bool) -> ParseResult<'a> {
+        let v = get_token_value!(self, token);
+        let slash = match v.find('/') {
+            Some(slash) if v != "/" => slash,
+            _ => {return self.resolve_unqualified_sym(token, true);}
+        };
+        let (qualifier, name) = (&v[..slash], &v[slash + 1..]);
+        if qualifier.is_empty() || name.is_empty() {
+            return Err(ParseError::new("Invalid qualified symbol.", token));
+        }
+        let namespace = match self.resolve_namespace(qualifier) {
+            Some(namespace) => namespace,
+            None => {return Err(ParseError::new(&format!("No such namespace: {}.", qualifier), token));}
+        };
+        if !private_access {
+            match self.check_private(token, &namespace, name) {
+                Err(e) => {return Err(e);}
+                _ => {}
+            }
+        }
+        Ok(self.new_module_reference(token, &namespace, name))
+    }
+
+
+    /// The error if `namespace/name` is a private var of the other registered module.
+    fn check_private(&self, token: Token<'a>, namespace: &str, name: &str) -> Result<(), ParseError<'a>> {
+        if namespace != self.namespace.get() &&
+            self.modules.borrow().iter().any(|m| m.namespace().as_ref().map(|n| &n[..]) == Some(namespace) && m.is_private_var(name)) {
+            return Err(ParseError::new(&format!("var: {}/{} is private.", namespace, name), token));
+        }
+        Ok(())
+    }
+
+
+    /// The namespace which the qualifier of `ns/name` stands for, the alias, the name of
+    /// the current or a required namespace, or the class imported, of `java.lang` or written with its package.
+    fn resolve_namespace(&self, qualifier: &str) -> Option<std::string::String> {
+        if qualifier == self.namespace.get() || qualifier == CORE_NAMESPACE ||
+            self.module_info.requires_namespace(qualifier) {
+            return Some(qualifier.to_string());
+        }
+        match self.module_info.resolve_alias(qualifier) {
+            Some(namespace) => {return Some(namespace);}
+            None => {}
+        }
+        match self.module_info.resolve_class(qualifier) {
+            Some(class) => {return Some(class);}
+            None => {}
+        }
+        if JAVA_LANG_CLASSES.contains(&qualifier) {
+            return Some(format!("java.lang.{}", qualifier));
+        }
+        let class = qualifier.rsplit('.').next().unwrap();
+        if qualifier.contains('.') && class.chars().next().map_or(false, |c| c.is_uppercase()) {
+            return Some(qualifier.to_string());
+        }
+        None
+    }
+
+
+    /// `ns/name` of the canonical namespace, the children are the symbols of them.
+    fn new_module_reference(&self, token: Token<'a>, namespace: &str, name: &'a str) -> &'a Ast<'a> {
+        let namespace = self.literal_buffer.find(self.literal_buffer.get(namespace));
+        let mr = Ast::new_module_reference(self.zone_allocator, token);
+        mr.add_child(Ast::new_symbol(self.zone_allocator, token, namespace, SymbolMode::Var(SymbolDepth::Depth(0))));
+        mr.add_child(Ast::new_symbol(self.zone_allocator, token, name, SymbolMode::Var(SymbolDepth::Depth(0))));
+        mr
+    }
+
+
+    /// `::name` is the keyword of the current namespace and `::alias/name` of the aliased one.
+    fn parse_auto_resolved_keyword(&self, token: Token<'a>) -> ParseResult<'a> {
+        let v = &get_token_value!(self, token)[2..];
+        let (namespace, name) = match v.find('/') {
+            Some(slash) => {
+                match self.module_info.resolve_alias(&v[..slash]) {
+                    Some(namespace) => (namespace, &v[slash + 1..]),
+                    None => {return Err(ParseError::new(&format!("No such namespace: {}.", &v[..slash]), token));}
+                }
+            }
+            None => (self.namespace.get().to_string(), v)
+        };
+        if name.is_empty() {
+            return Err(ParseError::new("Invalid keyword.", token));
+        }
+        let id = self.literal_buffer.get(&format!(":{}/{}", namespace, name));
+        Ok(Ast::new_keyword(self.zone_allocator, token, self.literal_buffer.find(id)))
+    }
+
+
+    /// The symbol bound in the scope, or the var referred by `:refer` of the require
+    /// if `refer` is true.
+    fn resolve_unqualified_sym(&self, token: Token<'a>, refer: bool) -> ParseResult<'a> {
+        let v = get_token_value!(self, token);
+        let sym = Ast::new_symbol(self.zone_allocator, token, v, SymbolMode::Unresolved);
+        match self.find_scope(sym) {
+            Some((d, s)) => {
+                match s.symbol_mode() {
+                    SymbolMode::Parameter{index, depth} => {
+                        sym.set_symbol_mode(SymbolMode::Parameter {index: index, depth: SymbolDepth::Depth(d)});
+                    },
+                    SymbolMode::RestParameter{index, depth} => {
+                        sym.set_symbol_mode(SymbolMode::RestParameter {index: index, depth: SymbolDepth::Depth(d)});
+                    },
+                    _ => {sym.set_symbol_mode(SymbolMode::Var(SymbolDepth::Depth(d)));}
+                }
+            }
+            None if refer => {
+                match self.module_info.referred_namespace(v, &self.modules.borrow()) {
+                    Some(namespace) => {
+                        return match self.check_private(token, &namespace, v) {
+                            Ok(()) => Ok(self.new_module_reference(token, &namespace, v)),
+                            Err(e) => Err(e)
+                        };
+                    }
+                    None => {}
+                }
+            }
+            None => {}
+        }
+        Ok(sym)
+    }
+
+
+    fn parse_number(&self, token: Token<'a>) -> Result<&'a Ast<'a>, &'static str> {
+        let v = get_token_value!(self, token);
+        let negative = v.starts_with('-');
+        let digits = if negative {&v[1..]} else {v};
+        let za = self.zone_allocator;
+
+        match token.kind() {
+            TokenKind::Int => {
+                self.parse_integer(digits, negative, 10).map(|i| self.new_integer(token, i))
+            }
+            TokenKind::Hex => {
+                self.parse_integer(&digits[2..], negative, 16).map(|i| self.new_integer(token, i))
+            }
+            TokenKind::Binary => {
+                self.parse_integer(&digits[2..], negative, 2).map(|i| self.new_integer(token, i))
+            }
+            TokenKind::Radix => {
+                let mark = digits.find(|c| c == 'r' || c == 'R').unwrap();
+                match digits[..mark].parse::<u32>() {
+                    Ok(radix) if radix >= 2 && radix <= 36 => {
+                        self.parse_integer(&digits[mark + 1..], negative, radix).map(|i| self.new_integer(token, i))
+                    }
+                    _ => Result::Err("Radix must be in range [2, 36].")
+                }
+            }
+            TokenKind::Long => {
+                self.parse_integer(&digits[..digits.len() - 1], negative, 10).map(|i| Ast::new_long(za, token, i))
+            }
+            TokenKind::BigNumber => {
+                let trimmed = digits[..digits.len() - 1].trim_left_matches('0');
+                let canonical = if trimmed.is_empty() {
+                    "0".to_string()
+                } else if negative {
+                    format!("-{}", trimmed)
+                } else {
+                    trimmed.to_string()
+                };
+                let id = self.literal_buffer.get(&canonical);
+                Ok(Ast::new_big_integer(za, token, self.literal_buffer.find(id)))
+            }
+            TokenKind::BigDecimal => {
+                let id = self.literal_buffer.get(&v[..v.len() - 1]);
+                Ok(Ast::new_big_decimal(za, token, self.literal_buffer.find(id)))
+            }
+            TokenKind::Ratio => {
+                let slash = digits.find('/').unwrap();
+                let numerator = try!(self.parse_integer(&digits[..slash], negative, 10));
+                let denominator = try!(self.parse_integer(&digits[slash + 1..], false, 10));
+                if denominator == 0 {
+                    return Result::Err("Divide by zero in ratio.");
+                }
+                let (mut a, mut b) = (numerator, denominator);
+                while b != 0 {
+                    let r = a % b;
+                    a = b;
+                    b = r;
+                }
+                let gcd = a.abs();
+                if denominator / gcd == 1 {
+                    return Ok(self.new_integer(token, numerator / gcd));
+                }
+                Ok(Ast::new_ratio(za, token, numerator / gcd, denominator / gcd))
+            }
+            _ => Result::Err("Invalid number.")
+        }
+    }
+
+
+    /// Read digits in the radix, reporting the overflow of 64 bit integer.
+    fn parse_integer(&self, digits: &str, negative: bool, radix: u32) -> Result<i64, &'static str> {
+        let mut result: i64 = 0;
+        for c in digits.chars() {
+            let d = match c.to_digit(radix) {
+                Some(d) => d as i64,
+                None => {
+                    return Result::Err("Invalid digit for the radix.");
+                }
+            };
+            let next = result.checked_mul(radix as i64).and_then(|r| {
+                if negative {r.checked_sub(d)} else {r.checked_add(d)}
+            });
+            match next {
+                Some(r) => result = r,
+                None => {
+                    return Result::Err("Integer literal is out of 64 bit range, use N suffix for big integer.");
+                }
+            }
+        }
+        Ok(result)
+    }
+
+
+    /// Integer fits in 32 bit, otherwise Long.
+    fn new_integer(&self, token: Token<'a>, value: i64) -> &'a Ast<'a> {
+        if value >= i32::MIN as i64 && value <= i32::MAX as i64 {
+            return Ast::new_integer(self.zone_allocator, token, value as i32);
+        }
+        Ast::new_long(self.zone_allocator, token, value)
+    }
+
+
+    /// Decode the escape sequences of the string literal.
+    fn unescape_string(&self, raw: &'a str, token: Token<'a>) -> Result<std::string::String, ParseError<'a>> {
+        let mut buf = std::string::String::with_capacity(raw.len());
+        let mut chars = raw.char_indices().peekable();
+        loop {
+            let (i, c) = match chars.next() {
+                Some(ic) => ic,
+                None => {
+                    return Ok(buf);
+                }
+            };
+            if c != '\\' {
+                buf.push(c);
+                continue;
+            }
+
+            let (_, e) = chars.next().unwrap();
+            let decoded = match e {
+                'n' => Ok('\n'),
+                't' => Ok('\t'),
+                'r' => Ok('\r'),
+                '"' => Ok('"'),
+                '\\' => Ok('\\'),
+                'b' => Ok('\u{8}'),
+                'f' => Ok('\u{c}'),
+                'u' => {
+                    let mut end = i + 2;
+                    for _ in 0..4 {
+                        match chars.peek() {
+                            Some(&(j, h)) if h.is_digit(16) => {
+                                chars.next();
+                                end = j + 1;
+                            }
+                            _ => break
+                        }
+                    }
+                    if end - i != 6 {
+                        Err("Invalid unicode escape sequence, \\u expected 4 hex digits.")
+                    } else {
+                        match self.parse_unicode_escape_seq(&raw[i + 2..end]).map(|v| std::char::from_u32(v as u32)) {
+                            Ok(Some(c)) => Ok(c),
+                            _ => Err("Invalid unicode escape sequence, surrogate code point.")
+                        }
+                    }
+                }
+                '0'...'7' => {
+                    let mut end = i + 2;
+                    for _ in 0..2 {
+                        match chars.peek() {
+                            Some(&(j, o)) if o.is_digit(8) => {
+                                chars.next();
+                                end = j + 1;
+                            }
+                            _ => break
+                        }
+                    }
+                    self.parse_octal_escape_seq(&raw[i + 1..end]).map(|v| v as u8 as char)
+                }
+                _ => Err("Unsupported escape character.")
+            };
+
+            match decoded {
+                Ok(c) => buf.push(c),
+                Err(message) => {
+                    let end = match chars.peek() {
+                        Some(&(j, _)) => j,
+                        None => raw.len()
+                    };
+                    let info = self.literal_source_info(token, 1, raw, i, end);
+                    return Err(ParseError::new(message, Token::new(info, TokenKind::String)));
+                }
+            }
+        }
+    }
+
+
+    /// Syntax error of the regexp literal, located at the offending char of the pattern.
+    fn regex_error(&self, token: Token<'a>, pattern: &str, err: regex::Error) -> ParseError<'a> {
+        match err {
+            regex::Error::Syntax(ref e) => {
+                let mut offsets = pattern.char_indices().map(|(i, _)| i).skip(e.position());
+                let from = offsets.next().unwrap_or(pattern.len());
+                let to = offsets.next().unwrap_or(pattern.len());
+                let info = self.literal_source_info(token, 2, pattern, from, to);
+                let kind = e.kind().to_string();
+                let message = format!("Invalid regular expression, {}.", kind.trim_right_matches('.'));
+                ParseError::new(&message, Token::new(info, TokenKind::Regexp))
+            }
+            _ => ParseError::new("Regular expression is too big.", token)
+        }
+    }
+
+
+    /// Source range of raw[from..to] of the string or regexp literal token,
+    /// `prefix` is the length of the opening delimiter.
+    fn literal_source_info(&self, token: Token<'a>, prefix: usize, raw: &str, from: usize, to: usize) -> SourceInfo<'a> {
+        let base = token.info();
+        let mut line = base.line();
+        let mut pos = base.pos() + prefix as i32;
+        for c in raw[..from].chars() {
+            if c == '\n' {
+                line += 1;
+                pos = 1;
+            } else {
+                pos += 1;
+            }
+        }
+        let start = SourceInfo::new(base.start() + prefix + from, pos, line, self.module_info);
+        let end = SourceInfo::new(base.start() + prefix + to, pos + raw[from..to].chars().count() as i32, line, self.module_info);
+        start.extend(end)
+    }
+
+
+    /// Decode the character literal such as \a, \newline, \o101 or \u0041 into a code point.
+    fn parse_char_literal(&self, literal: &'a str) -> Result<i32, &'static str> {
+        let name = &literal[1..];
+        let mut chars = name.chars();
+        let first = chars.next().unwrap();
+        if chars.next().is_none() {
+            return Ok(first as i32);
+        }
+
+        match name {
+            "newline" => Ok('\n' as i32),
+            "space" => Ok(' ' as i32),
+            "tab" => Ok('\t' as i32),
+            "return" => Ok('\r' as i32),
+            "formfeed" => Ok(0x0c),
+            "backspace" => Ok(0x08),
+            _ => {
+                if first == 'u' {
+                    if name.len() != 5 {
+                        return Result::Err("Invalid unicode character, \\u expected 4 hex digits.");
+                    }
+                    return match self.parse_unicode_escape_seq(&name[1..]) {
+                        Ok(v) if v >= 0xD800 && v <= 0xDFFF => Result::Err("Invalid unicode character, surrogate code point."),
+                        r => r
+                    };
+                }
+                if first == 'o' {
+                    return self.parse_octal_escape_seq(&name[1..]);
+                }
+                Result::Err("Unsupported character name.")
+            }
+        }
+    }
+
+
+    fn parse_unicode_escape_seq(&self, ue: &str) -> Result<i32, &'static str> {
+        let mut result: i32 = 0;
+        for u in ue.bytes() {
+            let v = self.to_hex_value(u);
+            if v < 0 {
+                return Result::Err("Invalid unicode sequence.");
+            }
+            result = result * 16 + v;
+        }
+        Ok(result)
+    }
+
+
+    /// Decode 1 to 3 octal digits, which must be in range 0 to 0377.
+    fn parse_octal_escape_seq(&self, oe: &str) -> Result<i32, &'static str> {
+        if oe.len() == 0 || oe.len() > 3 {
+            return Result::Err("Invalid octal escape sequence, 1 to 3 octal digits expected.");
+        }
+        let mut result: i32 = 0;
+        for o in oe.bytes() {
+            if o < '0' as u8 || o > '7' as u8 {
+                return Result::Err("Invalid digit in octal escape sequence.");
+            }
+            result = result * 8 + (o - '0' as u8) as i32;
+        }
+        if result > 0o377 {
+            return Result::Err("Octal escape sequence must be in range [0, 377].");
+        }
+        Ok(result)
+    }
+
+
+    fn to_hex_value(&self, uchar: u8) -> i32 {
+        if uchar >= '0' as u8 && uchar <= '9' as u8 {
+            return (uchar as i32 - '0' as i32) as i32;
+        } else if uchar >= 'a' as u8 && uchar <= 'f' as u8 {
+            return (uchar as i32 - 'a' as i32 + 10) as i32;
+        } else if uchar >= 'A' as u8 && uchar <= 'F' as u8 {
+            return (uchar as i32 - 'A' as i32 + 10);
+        }
+        return -1;
+    }
+
+
+    /// The special form named by the symbol at the head of a list.
+    /// A local binding, such as a let binding or a parameter, of the same name
+    /// shadows the special form and the list is parsed as a call.
+    fn special_form(&self, token: Token<'a>) -> Option<SpecialForm> {
+        match self.builtin_token_registry.special_form(token.value()) {
+            Some(form) => {
+                let sym = Ast::new_symbol(self.zone_allocator, token, get_token_value!(self, token), SymbolMode::Unresolved);
+                match self.find_scope(sym) {
+                    Some(_) => None,
+                    None => Some(form)
+                }
+            }
+            None => None
+        }
+    }
+
+
+    /// Error for the invalid token, with the reason found by the scanner if any.
+    fn invalid_token(&self, token: Token<'a>) -> ParseError<'a> {
+        if self.scanner.is_incomplete() {
+            return ParseError::new_incomplete(get_token_value!(self, token), token);
+        }
+        if token.value() >= 0 {
+            return ParseError::new(get_token_value!(self, token), token);
+        }
+        ParseError::new("Invalid token.", token)
+    }
+
+
+    /// Read the next token, dropping the forms commented out by #_.
+    fn next_token(&self) -> Result<Token<'a>, ParseError<'a>> {
+        match self.peek_token() {
+            Ok(_) => Result::Ok(self.scan()),
+            Err(e) => Result::Err(e)
+        }
+    }
+
+
+    /// Consume a token, keeping track of the open delimiters.
+    /// A closing delimiter closes the innermost form it matches, the forms opened inside that
+    /// form are closed together, and a closing delimiter which matches nothing is ignored.
+    fn scan(&self) -> Token<'a> {
+        let token = self.scanner.scan();
+        let opening = match token.kind() {
+            TokenKind::LeftParen | TokenKind::ShortLambdaBegin => {
+                self.delimiters.borrow_mut().push(TokenKind::RightParen);
+                None
+            }
+            TokenKind::LeftBracket => {
+                self.delimiters.borrow_mut().push(TokenKind::RightBracket);
+                None
+            }
+            TokenKind::LeftBrace | TokenKind::SetBegin => {
+                self.delimiters.borrow_mut().push(TokenKind::RightBrace);
+                None
+            }
+            TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace => {
+                self.delimiters.borrow().iter().rposition(|kind| *kind == token.kind())
+            }
+            _ => None
+        };
+        match opening {
+            Some(index) => self.delimiters.borrow_mut().truncate(index),
+            None => {}
+        }
+        if !token.is(TokenKind::Eof) {
+            self.last_token.set(Some(token));
+        }
+        token
+    }
+
+
+    /// Record the error of the form beginning at `token` and skip the rest of the form
+    /// until the delimiters opened after `depth` are closed.
+    /// Returns the Error node which stands for the form in the partial Ast.
+    fn recover(&self, depth: usize, token: Token<'a>, e: ParseError<'a>) -> &'a Ast<'a> {
+        let error = Ast::new_error(self.zone_allocator, token, e.message());
+        self.errors.borrow_mut().push(e);
+        while self.delimiters.borrow().len() > depth {
+            if self.scan().is(TokenKind::Eof) {
+                break;
+            }
+        }
+        match self.last_token.get() {
+            Some(last) => {
+                if last.info().start() >= token.info().start() {
+                    error.extend_source_info(last.info());
+                }
+            }
+            None => {}
+        }
+        error
+    }
+
+
+    /// The next token without consuming it, the forms commented out by #_ are dropped.
+    fn peek_token(&self) -> Result<Token<'a>, ParseError<'a>> {
+        loop {
+            let token = self.scanner.peek();
+            if !token.is(TokenKind::Discard) {
+                return Result::Ok(token);
+            }
+            self.scan();
+
+            let next = next_token!(self);
+            if next.is(TokenKind::Eof) {
+                return Result::Err(ParseError::new_incomplete("#_ expected a form to discard.", next));
+            }
+            let discarded = Ast::new_list(self.zone_allocator, token);
+            match self.do_parse_form(discarded, next, |ast: &'a Ast<'a>| {}) {
+                Ok(_) => {},
+                Err(e) => {return Result::Err(e);}
+            }
+        }
+    }
+
+
+    /// Create a keyword which is not written in the source, such as `:tag` of ^Type.
+    fn new_builtin_keyword(&self, token: Token<'a>, name: &str) -> &'a Ast<'a> {
+        let id = self.literal_buffer.get(name);
+        let token = Token::new_value(token.info(), id, TokenKind::Keyword);
+        Ast::new_keyword(self.zone_allocator, token, self.literal_buffer.find(id))
+    }
+
+
+    /// Create a symbol which is not written in the source, such as `var` of #'sym.
+    fn new_builtin_symbol(&self, token: Token<'a>, name: &str) -> &'a Ast<'a> {
+        let id = self.literal_buffer.get(name);
+        let token = Token::new_value(token.info(), id, TokenKind::Symbol);
+        Ast::new_symbol(self.zone_allocator, token, self.literal_buffer.find(id), SymbolMode::Unresolved)
+    }
+
+
+    /// Whether two forms are the same value, used to find duplicated set elements and map keys.
+    /// Numbers are compared by their value, so `1` and `0x1` are the same, the other leaves by their text.
+    fn is_same_form(&self, a: &'a Ast<'a>, b: &'a Ast<'a>) -> bool {
+        match (a.children(), b.children()) {
+            (Some(ac), Some(bc)) => {
+                a.token().map(|t| t.kind()) == b.token().map(|t| t.kind()) &&
+                    ac.len() == bc.len() &&
+                    ac.iter().zip(bc.iter()).all(|(x, y)| self.is_same_form(x, y))
+            }
+            (None, None) => {
+                match self.is_same_number(a, b) {
+                    Some(same) => {return same;}
+                    None => {}
+                }
+                match (a.token(), b.token()) {
+                    (Some(x), Some(y)) => x.kind() == y.kind() && x.value() != -1 && x.value() == y.value(),
+                    _ => false
+                }
+            }
+            _ => false
+        }
+    }
+
+
+    /// Whether the numbers are the same value, None if either is not Int, Long, Float or Ratio.
+    /// As in Clojure, an integer is not the same as the float of the same value.
+    fn is_same_number(&self, a: &'a Ast<'a>, b: &'a Ast<'a>) -> Option<bool> {
+        let integer = |ast: &'a Ast<'a>| ast.int_value().map(|i| i as i64).or(ast.long_value());
+        let number = |ast: &'a Ast<'a>| integer(ast).is_some() || ast.ratio_value().is_some() || ast.double_value().is_some();
+        if !number(a) || !number(b) {
+            return None;
+        }
+        match (integer(a), integer(b)) {
+            (Some(x), Some(y)) => {return Some(x == y);}
+            _ => {}
+        }
+        match (a.ratio_value(), b.ratio_value()) {
+            (Some(x), Some(y)) => {return Some(x == y);}
+            _ => {}
+        }
+        match (a.double_value(), b.double_value()) {
+            (Some(x), Some(y)) => Some(x == y),
+            _ => Some(false)
+        }
+    }
+
+
+    /// Finish the form at the closing token.
+    fn close(&self, ast: &'a Ast<'a>, token: Token<'a>) -> ParseResult<'a> {
+        ast.extend_source_info(token.info());
+        Ok(ast)
+    }
+
+
+    /// Stretch the source range of `ast` to cover `child`.
+    fn extend_to(&self, ast: &'a Ast<'a>, child: &'a Ast<'a>) {
+        match child.source_info() {
+            Some(info) => ast.extend_source_info(info),
+            None => {}
+        }
+    }
+
+
+    fn find_scope(&self, ast: &'a Ast<'a>) -> Option<(u32, &'a Ast<'a>)> {
+        self.scope_handler.scope().find(ast)
+    }
+
+
+    fn intern_scope(&self, ast: &'a Ast<'a>) {
+        self.scope_handler.scope().intern(ast);
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use parser::literal_buffer::LiteralBuffer;
+    use parser::moduleinfo::ModuleInfo;
+    use internal::ast::*;
+    use internal::heap::zone::ZoneAllocator;
+    use std::io::Cursor;
+
+    /// Print the form as an s-expression.
+    fn sexp<'a>(ast: &'a Ast<'a>) -> std::string::String {
+        let join = |ast: &'a Ast<'a>, sep: &str| {
+            ast.children().unwrap().iter().map(|c| sexp(c)).collect::<Vec<std::string::String>>().join(sep)
+        };
+        match ast {
+            &Ast::List(_) => format!("({})", join(ast, " ")),
+            &Ast::Vector(_) => format!("[{}]", join(ast, " ")),
+            &Ast::ModuleReference(_) => join(ast, "/"),
+            &Ast::Symbol(_) => ast.symbol_value().unwrap().to_string(),
+            &Ast::Keyword(_) => ast.string_value().unwrap().to_string(),
+            &Ast::String(_) => format!("{:?}", ast.string_value().unwrap()),
+            _ => format!("{:?}", ast.int_value())
+        }
+    }
+
+    fn parse<'a>(code: &str, module_info: &'a ModuleInfo, lb: &'a LiteralBuffer<'a>, zone_allocator: &'a ZoneAllocator) -> ParseResult<'a> {
+        let parser = Parser::new_from_code(module_info, code, lb, zone_allocator);
+        parser.parse().map_err(|e| e.into_errors().remove(0))
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let module = parse("(f #{1 :a} #_(g) #'x #?(:clj 1 :risp 2) #?(:clj 3) #?@(:default [4 5]))",
+                           &module_info, &lb, &zone_allocator).ok().unwrap();
+        let list = module.children().unwrap()[0];
+        let children = list.children().unwrap();
+        assert_eq!(children.len(), 6);
+        match children[1] {
+            &Ast::Set(_) => assert_eq!(children[1].children().unwrap().len(), 2),
+            _ => panic!("set expected")
+        }
+        let var = children[2].children().unwrap();
+        assert_eq!((var[0].token().map(|t| lb.find(t.value())), var[1].token().map(|t| lb.find(t.value()))),
+                   (Some("var"), Some("x")));
+        assert_eq!(children[3].int_value(), Some(2));
+        assert_eq!(children[4].int_value(), Some(4));
+        assert_eq!(children[5].int_value(), Some(5));
+
+        let module = parse("(def x #_1 2)", &module_info, &lb, &zone_allocator).ok().unwrap();
+        assert_eq!(module.children().unwrap()[0].def_expr().unwrap().int_value(), Some(2));
+    }
+
+    #[test]
+    fn test_dispatch_error() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        assert!(parse("(f #{1 [:a] [:a]})", &module_info, &lb, &zone_allocator).is_err());
+        for code in &["#{1 0x1}", "#{10L 0b1010}", "#{[1.5] [1.50]}", "#{1/2 2/4}", "{1 :a 0x1 :b}", "{:a 1 :a 2}"] {
+            assert!(parse(code, &module_info, &lb, &zone_allocator).is_err(), "{}", code);
+        }
+        let err = parse("#{1 2 0x1}", &module_info, &lb, &zone_allocator).err().unwrap();
+        assert_eq!(err.to_string(), "Duplicate set element.:test:7:1");
+        assert!(parse("#{1 1.0 1/2 0.5 2N} {1 :a 2 :b}", &module_info, &lb, &zone_allocator).is_ok());
+        assert!(parse("(f #'1)", &module_info, &lb, &zone_allocator).is_err());
+        assert!(parse("(f #?(:risp))", &module_info, &lb, &zone_allocator).is_err());
+        assert!(parse("(f #?@(:risp 1))", &module_info, &lb, &zone_allocator).is_err());
+        assert!(parse("(f) #_", &module_info, &lb, &zone_allocator).is_err());
+    }
+
+    #[test]
+    fn test_char_literal() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let module = parse("(f \\a \\( \\newline \\space \\tab \\return \\formfeed \\backspace \\o101 \\u3042)",
+                           &module_info, &lb, &zone_allocator).ok().unwrap();
+        let chars: Vec<Option<i32>> = module.children().unwrap()[0].children().unwrap()[1..].iter().map(|c| c.uchar_value()).collect();
+        assert_eq!(chars, vec![Some(97), Some(40), Some(10), Some(32), Some(9), Some(13), Some(12), Some(8), Some(65), Some(0x3042)]);
+
+        for code in &["(f \\foo)", "(f \\u12)", "(f \\uD800)", "(f \\o400)", "(f \\o9)"] {
+            assert!(parse(code, &module_info, &lb, &zone_allocator).is_err());
+        }
+    }
+
+    #[test]
+    fn test_number() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let module = parse("(f 0xFF -0b101 36rZZ 4294967296 10L -0009N 1.50M 2/4 4/2 -9223372036854775808)",
+                           &module_info, &lb, &zone_allocator).ok().unwrap();
+        let c = module.children().unwrap()[0].children().unwrap();
+        assert_eq!(c[1].int_value(), Some(255));
+        assert_eq!(c[2].int_value(), Some(-5));
+        assert_eq!(c[3].int_value(), Some(1295));
+        assert_eq!(c[4].long_value(), Some(4294967296));
+        assert_eq!(c[5].long_value(), Some(10));
+        assert_eq!(c[6].big_number_value(), Some("-9"));
+        assert_eq!(c[7].big_number_value(), Some("1.50"));
+        assert_eq!(c[8].ratio_value(), Some((1, 2)));
+        assert_eq!(c[9].int_value(), Some(2));
+        assert_eq!(c[10].long_value(), Some(-9223372036854775808));
+
+        for code in &["(f 9223372036854775808)", "(f 0x1FFFFFFFFFFFFFFFF)", "(f 37r1)", "(f 2r3)", "(f 1/0)"] {
+            assert!(parse(code, &module_info, &lb, &zone_allocator).is_err());
+        }
+    }
+
+    #[test]
+    fn test_string_escape() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let module = parse(r#"(f "a\nb\t\"\\\u3042\101\0")"#, &module_info, &lb, &zone_allocator).ok().unwrap();
+        let string = module.children().unwrap()[0].children().unwrap()[1];
+        assert_eq!(string.string_value(), Some("a\nb\t\"\\\u{3042}A\u{0}"));
+        assert_eq!(string.raw_string_value(), Some(r#"a\nb\t\"\\\u3042\101\0"#));
+
+        let err = parse("(f \"ab\ncd\\q\")", &module_info, &lb, &zone_allocator).err().unwrap();
+        assert_eq!(err.to_string(), "Unsupported escape character.:test:3:2");
+        let err = parse("(f \"ab", &module_info, &lb, &zone_allocator).err().unwrap();
+        assert_eq!(err.to_string(), "Unterminated string literal.:test:4:1");
+        assert!(parse(r#"(f "\u12")"#, &module_info, &lb, &zone_allocator).is_err());
+        assert!(parse(r#"(f "\400")"#, &module_info, &lb, &zone_allocator).is_err());
+    }
+
+    #[test]
+    fn test_incomplete_input() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        for code in &["(f [1 2", "(f \"abc", "(f #_", "(f #{1", "(let", "(let [x", "(let [x 1", "(let [x (foo",
+                      "(defmacro m", "(defmacro", "#'", "(def"] {
+            let err = parse(code, &module_info, &lb, &zone_allocator).err().unwrap();
+            assert!(err.is_incomplete(), "{} {}", code, err);
+        }
+        assert!(!parse("(f ]", &module_info, &lb, &zone_allocator).err().unwrap().is_incomplete());
+        assert!(Parser::read_string(&module_info, "(let [x (foo", &lb, &zone_allocator).err().unwrap().is_incomplete());
+        assert!(!parse("(let [x 1 2]", &module_info, &lb, &zone_allocator).err().unwrap().is_incomplete());
+
+        let reader = Box::new(Cursor::new("(f \"あい\" 1)\n(g [2])".as_bytes()));
+        let parser = Parser::new_from_reader(&module_info, reader, &lb, &zone_allocator);
+        let module = parser.parse().ok().unwrap();
+        assert_eq!(module.children().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_if() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let module = parse("(if a b) (if a b #_ c c) (x)", &module_info, &lb, &zone_allocator).ok().unwrap();
+        assert_eq!(module.children().unwrap().len(), 3);
+        assert!(parse("(if a b c d)", &module_info, &lb, &zone_allocator).is_err());
+    }
+
+    #[test]
+    fn test_special_form() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let module = parse("(let[x 1] x) (if\nx 1 2) (map fn xs) (fn [if] (if 1))", &module_info, &lb, &zone_allocator).ok().unwrap();
+        let children = module.children().unwrap();
+        match children[0] {&Ast::Let(_) => {}, _ => panic!("let expected")}
+        match children[1] {&Ast::If(_) => {}, _ => panic!("if expected")}
+        match children[2] {&Ast::List(_) => {}, _ => panic!("list expected")}
+        match children[3] {
+            &Ast::Lambda(_) => {
+                match children[3].lambda_body().unwrap()[0] {&Ast::List(_) => {}, _ => panic!("shadowed if expected")}
+            }
+            _ => panic!("fn expected")
+        }
+
+        let err = parse("(def if 1)", &module_info, &lb, &zone_allocator).err().unwrap();
+        assert_eq!(err.to_string(), "Special forms cannot be redefined.:test:6:1");
+
+        let module = parse("(let [x 1] (let [x x y x] y))", &module_info, &lb, &zone_allocator).ok().unwrap();
+        let inner = module.children().unwrap()[0].let_body()[0];
+        let bindings = inner.let_bindings();
+        assert!(match bindings[0].1.symbol_mode() {SymbolMode::Var(SymbolDepth::Depth(1)) => true, _ => false});
+        assert!(match bindings[1].1.symbol_mode() {SymbolMode::Var(SymbolDepth::Depth(0)) => true, _ => false});
+        assert!(match inner.let_body()[0].symbol_mode() {SymbolMode::Var(SymbolDepth::Depth(0)) => true, _ => false});
+    }
+
+    #[test]
+    fn test_metadata() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let code = "(def ^{:private true :doc \"d\"} x 1) (f ^:a ^{:a false :b 1} ^String [y])";
+        let module = parse(code, &module_info, &lb, &zone_allocator).ok().unwrap();
+        let children = module.children().unwrap();
+        let name = children[0].def_name().unwrap();
+        assert_eq!(name.meta_value(":private").unwrap().boolean_value(), Some(true));
+        assert_eq!(name.meta_value(":doc").unwrap().string_value(), Some("d"));
+
+        let vector = children[1].children().unwrap()[1];
+        assert_eq!(vector.meta_value(":a").unwrap().boolean_value(), Some(true));
+        assert_eq!(vector.meta_value(":b").unwrap().int_value(), Some(1));
+        assert!(vector.meta_value(":tag").is_some());
+        assert_eq!(vector.meta().unwrap().children().unwrap().len(), 6);
+
+        assert!(parse("(f ^1 x)", &module_info, &lb, &zone_allocator).is_err());
+    }
+
+    #[test]
+    fn test_syntax_quote() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let module = parse("(defmacro m [x ys] `(if ~x ~@ys a# [a# :k]))", &module_info, &lb, &zone_allocator).ok().unwrap();
+        let defmacro = module.children().unwrap()[0];
+        let body = defmacro.macro_body().unwrap();
+        assert_eq!(sexp(body[0]),
+                   "(risp.core/seq (risp.core/concat (risp.core/list (quote if)) (risp.core/list x) ys \
+                    (risp.core/list (quote a__1__auto__)) (risp.core/list (risp.core/apply risp.core/vector \
+                    (risp.core/seq (risp.core/concat (risp.core/list (quote a__1__auto__)) (risp.core/list :k)))))))");
+
+        let module = parse("(f `(g ~@xs) `~y `() ``z)", &module_info, &lb, &zone_allocator).ok().unwrap();
+        let args = module.children().unwrap()[0].children().unwrap();
+        assert_eq!(sexp(args[1]), "(risp.core/seq (risp.core/concat (risp.core/list (quote user/g)) xs))");
+        assert_eq!(sexp(args[2]), "y");
+        assert_eq!(sexp(args[3]), "(risp.core/list)");
+        assert_eq!(sexp(args[4]), "(risp.core/seq (risp.core/concat (risp.core/list (quote quote)) (risp.core/list (quote user/z))))");
+
+        assert!(parse("(f ~x)", &module_info, &lb, &zone_allocator).is_err());
+        assert!(parse("(f `~@x)", &module_info, &lb, &zone_allocator).is_err());
+    }
+
+    #[test]
+    fn test_reader_macro() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let module = parse("(f @a '(if b) #'c (quote [d]))", &module_info, &lb, &zone_allocator).ok().unwrap();
+        let args = module.children().unwrap()[0].children().unwrap();
+        assert_eq!(sexp(args[1]), "(deref a)");
+        assert_eq!(sexp(args[2]), "(quote (if b))");
+        assert_eq!(sexp(args[3]), "(var c)");
+        assert_eq!(sexp(args[4]), "(quote [d])");
+        let info = args[1].source_info().unwrap();
+        assert_eq!((info.start(), info.end()), (3, 5));
+
+        assert!(parse("(f @)", &module_info, &lb, &zone_allocator).is_err());
+        assert!(parse("(quote a b)", &module_info, &lb, &zone_allocator).is_err());
+    }
+
+    #[test]
+    fn test_tagged_literal() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let code = "(f #inst \"2016-02-29T10:20:30.5+09:00\" #inst \"2016\" #uuid \"f81d4fae-7dec-11d0-a765-00a0c91e6bf6\")";
+        let module = parse(code, &module_info, &lb, &zone_allocator).ok().unwrap();
+        let args = module.children().unwrap()[0].children().unwrap();
+        assert_eq!(sexp(args[1]), "(risp.core/inst \"2016-02-29T10:20:30.5+09:00\")");
+        assert_eq!(sexp(args[3]), "(risp.core/uuid \"f81d4fae-7dec-11d0-a765-00a0c91e6bf6\")");
+
+        let err = parse("(f #inst \"2015-02-29\")", &module_info, &lb, &zone_allocator).err().unwrap();
+        assert_eq!(err.to_string(), "Invalid timestamp 2015-02-29.:test:4:1");
+        assert!(parse("(f #uuid \"f81d4fae\")", &module_info, &lb, &zone_allocator).is_err());
+        let err = parse("(f #point [1 2])", &module_info, &lb, &zone_allocator).err().unwrap();
+        assert_eq!(err.to_string(), "No reader function for tag point.:test:4:1");
+
+        let parser = Parser::new_from_code(&module_info, "(f #twice 2)", &lb, &zone_allocator);
+        parser.register_tag_reader("twice", |parser: &Parser, form: &Ast| {
+            match form.int_value() {
+                Some(v) => Ok(Ast::new_integer(parser.zone_allocator(), form.token().unwrap(), v * 2)),
+                None => Err("#twice expected an integer.".to_string())
+            }
+        });
+        let module = parser.parse().ok().unwrap();
+        assert_eq!(module.children().unwrap()[0].children().unwrap()[1].int_value(), Some(4));
+    }
+
+    #[test]
+    fn test_regexp() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let module = parse("(f #\"a\\d+\")", &module_info, &lb, &zone_allocator).ok().unwrap();
+        let regexp = module.children().unwrap()[0].children().unwrap()[1];
+        assert_eq!(regexp.string_value(), Some("a\\d+"));
+        assert!(regexp.regex_value().unwrap().is_match("xa12"));
+
+        let err = parse("(f #\"あ(b\\q\")", &module_info, &lb, &zone_allocator).err().unwrap();
+        assert!(err.to_string().starts_with("Invalid regular expression, Unrecognized escape sequence: '\\q'.:"));
+        assert!(err.to_string().ends_with(":test:10:1"));
+        assert!(parse("(f #\"a(\")", &module_info, &lb, &zone_allocator).is_err());
+    }
+
+    #[test]
+    fn test_recovery() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let parser = Parser::new_from_code(&module_info, "(def x 1 2) (f (g ]) \\foo 3) (let [x] x)\n(h [1 2) (i)", &lb, &zone_allocator);
+        let errors = parser.parse().err().unwrap();
+        let messages: Vec<&str> = errors.errors().iter().map(|e| e.message()).collect();
+        assert_eq!(messages, vec!["def expected a name, an optional docstring and attr-map, and a value.", "Invalid Token.",
+                                  "Unsupported character name.", "let form expected symbol-value pair.", "Invalid Token."]);
+        assert!(!errors.is_incomplete());
+
+        let children = errors.module().children().unwrap();
+        assert_eq!(children.len(), 5);
+        assert!(children[0].is_error());
+        let info = children[0].source_info().unwrap();
+        assert_eq!((info.start(), info.end()), (0, 11));
+        assert_eq!(sexp(children[4]), "(i)");
+        let f = children[1].children().unwrap();
+        assert_eq!(f.len(), 4);
+        assert!(f[1].children().unwrap()[1].is_error());
+        assert!(f[2].is_error());
+        assert_eq!(f[3].int_value(), Some(3));
+        assert!(children[2].is_error());
+        let h = children[3].children().unwrap();
+        assert!(h[1].children().unwrap()[2].is_error());
+
+        let parser = Parser::new_from_code(&module_info, "(f 1) (g [1", &lb, &zone_allocator);
+        let errors = parser.parse().err().unwrap();
+        assert_eq!(errors.errors().len(), 1);
+        assert!(errors.is_incomplete());
+        assert_eq!(errors.module().children().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_top_level_form() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let module = parse("42 :kw [1 2] 'sym \"s\" #?@(:risp [x y]) (f)", &module_info, &lb, &zone_allocator).ok().unwrap();
+        let forms: Vec<std::string::String> = module.children().unwrap().iter().map(|c| sexp(c)).collect();
+        assert_eq!(forms, vec!["Some(42)", ":kw", "[Some(1) Some(2)]", "(quote sym)", "\"s\"", "x", "y", "(f)"]);
+
+        let err = parse("(f))", &module_info, &lb, &zone_allocator).err().unwrap();
+        assert_eq!(err.to_string(), "Unmatched delimiter.:test:4:1");
+    }
+
+    #[test]
+    fn test_parse_one() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let form = Parser::read_string(&module_info, " {:a [1 2]} ; comment\n", &lb, &zone_allocator).ok().unwrap();
+        match form {
+            &Ast::Map(_) => {},
+            _ => panic!("map expected")
+        }
+        assert_eq!(Parser::read_string(&module_info, "#_ x :kw", &lb, &zone_allocator).ok().unwrap().string_value(), Some(":kw"));
+
+        let err = Parser::read_string(&module_info, "(f) 1", &lb, &zone_allocator).err().unwrap();
+        assert_eq!(err.to_string(), "Unexpected input after the form.:test:5:1");
+        assert!(Parser::read_string(&module_info, "(f", &lb, &zone_allocator).err().unwrap().is_incomplete());
+        assert!(Parser::read_string(&module_info, "", &lb, &zone_allocator).err().unwrap().is_incomplete());
+        let err = Parser::read_string(&module_info, "#?@(:risp [x y])", &lb, &zone_allocator).err().unwrap();
+        assert_eq!(err.to_string(), "Single form expected, but #?@ spliced multiple forms.:test:1:1");
+        assert!(Parser::read_string(&module_info, "#?@(:risp [x])", &lb, &zone_allocator).is_ok());
+        assert!(parse("'#?@(:risp [x y])", &module_info, &lb, &zone_allocator).is_err());
+        assert!(parse("@#?@(:risp [x y])", &module_info, &lb, &zone_allocator).is_err());
+    }
+
+    #[test]
+    fn test_short_lambda() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let module = parse("(map #(inc %) xs) #(apply f %2 % %&) #(let [x 1] (+ x %1 %&))", &module_info, &lb, &zone_allocator).ok().unwrap();
+        let children = module.children().unwrap();
+
+        let sugar = children[0].children().unwrap()[1];
+        assert_eq!((sugar.lambda_sugar_arity(), sugar.lambda_sugar_variadic()), (Some(1), Some(false)));
+        let lambda = sugar.desugared_lambda().unwrap();
+        let args: Vec<std::string::String> = lambda.lambda_arguments().unwrap().iter().map(|a| sexp(a)).collect();
+        assert_eq!(args, vec!["p1__1#"]);
+        assert_eq!(sexp(lambda.lambda_body().unwrap()[0]), "(inc p1__1#)");
+
+        let sugar = children[1];
+        assert_eq!((sugar.lambda_sugar_arity(), sugar.lambda_sugar_variadic()), (Some(2), Some(true)));
+        let lambda = sugar.desugared_lambda().unwrap();
+        let args: Vec<std::string::String> = lambda.lambda_arguments().unwrap().iter().map(|a| sexp(a)).collect();
+        assert_eq!(args, vec!["p1__2#", "p2__2#"]);
+        assert_eq!(lambda.arity(), Some((2, true)));
+        assert_eq!(lambda.rest_parameter().map(|r| sexp(r)), Some("rest__2#".to_string()));
+        let body = lambda.lambda_body().unwrap()[0];
+        assert_eq!(sexp(body), "(apply f p2__2# p1__2# rest__2#)");
+        match body.children().unwrap()[4].symbol_mode() {
+            SymbolMode::RestParameter{index, depth: SymbolDepth::Depth(0)} => assert_eq!(index, 2),
+            _ => panic!("rest parameter expected")
+        }
+
+        let body = children[2].desugared_lambda().unwrap().lambda_body().unwrap()[0];
+        let add = body.let_body()[0];
+        match add.children().unwrap()[2].symbol_mode() {
+            SymbolMode::Parameter{index: 0, depth: SymbolDepth::Depth(1)} => {},
+            _ => panic!("parameter of the outer scope expected")
+        }
+
+        let err = parse("#(f #(g %))", &module_info, &lb, &zone_allocator).err().unwrap();
+        assert_eq!(err.to_string(), "Nested #()s are not allowed.:test:5:1");
+        assert!(parse("#(f %0)", &module_info, &lb, &zone_allocator).is_err());
+
+        let module = parse("(f %) '% '(a %) (fn [%] %) `#(f % %&)", &module_info, &lb, &zone_allocator).ok().unwrap();
+        let forms: Vec<std::string::String> = module.children().unwrap().iter().map(|c| sexp(c)).collect();
+        assert_eq!(forms[..3].to_vec(), vec!["(f %)", "(quote %)", "(quote (a %))"]);
+        let lambda = module.children().unwrap()[3];
+        assert_eq!(sexp(lambda.lambda_arguments().unwrap()[0]), "%");
+        match lambda.lambda_body().unwrap()[0].symbol_mode() {
+            SymbolMode::Parameter{index: 0, ..} => {},
+            _ => panic!("parameter expected")
+        }
+        assert_eq!(forms[4], "(risp.core/seq (risp.core/concat (risp.core/list (quote fn)) \
+                              (risp.core/list (risp.core/apply risp.core/vector (risp.core/seq (risp.core/concat \
+                              (risp.core/list (quote p1__1__2__auto__)) (risp.core/list (quote &)) \
+                              (risp.core/list (quote rest__1__3__auto__)))))) \
+                              (risp.core/list (risp.core/seq (risp.core/concat (risp.core/list (quote user/f)) \
+                              (risp.core/list (quote p1__1__2__auto__)) (risp.core/list (quote rest__1__3__auto__)))))))");
+    }
+
+    #[test]
+    fn test_rest_parameter() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let module = parse("(fn [a & more] (f a more)) (defmacro m [& body] (g body)) (fn [] 1)", &module_info, &lb, &zone_allocator).ok().unwrap();
+        let children = module.children().unwrap();
+
+        let lambda = children[0];
+        assert_eq!(lambda.arity(), Some((1, true)));
+        assert_eq!(sexp(lambda.rest_parameter().unwrap()), "more");
+        let body = lambda.lambda_body().unwrap()[0];
+        match body.children().unwrap()[2].symbol_mode() {
+            SymbolMode::RestParameter{index: 1, depth: SymbolDepth::Depth(0)} => {},
+            _ => panic!("rest parameter expected")
+        }
+        assert_eq!(children[1].arity(), Some((0, true)));
+        assert_eq!(children[2].arity(), Some((0, false)));
+
+        for code in &["(fn [a &] a)", "(fn [a & b c] a)", "(fn [& & b] b)", "(defmacro m [& 1] 1)"] {
+            assert!(parse(code, &module_info, &lb, &zone_allocator).is_err());
+        }
+        let err = parse("(fn [a & b c] a)", &module_info, &lb, &zone_allocator).err().unwrap();
+        assert_eq!(err.to_string(), "Only one parameter can follow &.:test:12:1");
+    }
+
+    #[test]
+    fn test_destructuring() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let bindings = |ast: &Ast| -> Vec<std::string::String> {
+            ast.let_bindings().iter().map(|&(s, v)| format!("{} {}", sexp(s), sexp(v))).collect()
+        };
+        let module = parse("(let [[a [b] & r :as all] v {:keys [x y] :or {y 2} :as m} w {z :z} u] (f a b r all x y m z))",
+                           &module_info, &lb, &zone_allocator).ok().unwrap();
+        let let_form = module.children().unwrap()[0];
+        assert_eq!(bindings(let_form), vec![
+            "vec__1 v", "all vec__1", "a (risp.core/nth vec__1 Some(0) None)",
+            "vec__2 (risp.core/nth vec__1 Some(1) None)", "b (risp.core/nth vec__2 Some(0) None)",
+            "r (risp.core/nthnext vec__1 Some(2))",
+            "map__3 w", "m map__3", "x (risp.core/get map__3 :x)", "y (risp.core/get map__3 :y Some(2))",
+            "map__4 u", "z (risp.core/get map__4 :z)"]);
+        let call = let_form.let_body()[0];
+        for arg in call.children().unwrap()[1..].iter() {
+            match arg.symbol_mode() {
+                SymbolMode::Var(SymbolDepth::Depth(0)) => {},
+                _ => panic!("let binding expected")
+            }
+        }
+
+        let module = parse("(fn [a [b c] & {:keys [d]}] (f a b d)) (defmacro m [[x]] x)", &module_info, &lb, &zone_allocator).ok().unwrap();
+        let lambda = module.children().unwrap()[0];
+        let args: Vec<std::string::String> = lambda.lambda_arguments().unwrap().iter().map(|a| sexp(a)).collect();
+        assert_eq!(args, vec!["a", "p__1"]);
+        assert_eq!(sexp(lambda.rest_parameter().unwrap()), "p__2");
+        let let_form = lambda.lambda_body().unwrap()[0];
+        assert_eq!(bindings(let_form), vec![
+            "vec__3 p__1", "b (risp.core/nth vec__3 Some(0) None)", "c (risp.core/nth vec__3 Some(1) None)",
+            "map__4 p__2", "d (risp.core/get map__4 :d)"]);
+        match let_form.let_bindings()[0].1.symbol_mode() {
+            SymbolMode::Parameter{index: 1, depth: SymbolDepth::Depth(1)} => {},
+            _ => panic!("parameter expected")
+        }
+        let call = let_form.let_body()[0].children().unwrap();
+        match call[1].symbol_mode() {
+            SymbolMode::Parameter{index: 0, depth: SymbolDepth::Depth(1)} => {},
+            _ => panic!("parameter expected")
+        }
+        let defmacro = module.children().unwrap()[1];
+        assert_eq!(sexp(defmacro.macro_body().unwrap()[0].let_body()[0]), "x");
+
+        let module = parse("(fn [^String s] s) (defn f [^long x [^:a y] & ^Seq {:keys [^int z]}] x)",
+                           &module_info, &lb, &zone_allocator).ok().unwrap();
+        let lambda = module.children().unwrap()[0];
+        assert!(lambda.lambda_arguments().unwrap()[0].meta_value(":tag").is_some());
+        let lambda = module.children().unwrap()[1].def_expr().unwrap();
+        assert!(lambda.lambda_arguments().unwrap()[0].meta_value(":tag").is_some());
+        let bindings = lambda.lambda_body().unwrap()[0].let_bindings();
+        let names: Vec<std::string::String> = bindings.iter().map(|&(s, _)| sexp(s)).collect();
+        assert_eq!(names, vec!["vec__3", "y", "map__4", "z"]);
+        assert_eq!(bindings[1].0.meta_value(":a").unwrap().boolean_value(), Some(true));
+        assert!(bindings[2].0.meta_value(":tag").is_some());
+        assert!(bindings[3].0.meta_value(":tag").is_some());
+        assert_eq!(sexp(bindings[3].1), "(risp.core/get map__4 :z)");
+
+        for code in &["(let [[a & b c] v] a)", "(let [[a :as] v] a)", "(let [{:keys [:a]} v] a)",
+                      "(let [[1] v] 1)", "(fn [[a/b]] 1)", "(let [{:or [a 1]} v] a)"] {
+            assert!(parse(code, &module_info, &lb, &zone_allocator).is_err());
+        }
+    }
+
+    #[test]
+    fn test_multi_arity_lambda() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let module = parse("(fn self [n] (self (dec n))) (fn f ([] (f 1)) ([x] x) ([x y & more] more))", &module_info, &lb, &zone_allocator).ok().unwrap();
+        let children = module.children().unwrap();
+
+        let lambda = children[0];
+        assert_eq!(sexp(lambda.lambda_name().unwrap()), "self");
+        assert_eq!(lambda.arity(), Some((1, false)));
+        let call = lambda.lambda_body().unwrap()[0];
+        assert!(match call.children().unwrap()[0].symbol_mode() {SymbolMode::Var(SymbolDepth::Depth(1)) => true, _ => false});
+        match call.children().unwrap()[1].children().unwrap()[1].symbol_mode() {
+            SymbolMode::Parameter{index: 0, depth: SymbolDepth::Depth(0)} => {},
+            _ => panic!("parameter expected")
+        }
+
+        let lambda = children[1];
+        assert_eq!(lambda.arity(), None);
+        let clauses = lambda.lambda_clauses().unwrap();
+        let arities: Vec<Option<(usize, bool)>> = clauses.iter().map(|c| c.arity()).collect();
+        assert_eq!(arities, vec![Some((0, false)), Some((1, false)), Some((2, true))]);
+        let call = clauses[0].lambda_body().unwrap()[0];
+        assert!(match call.children().unwrap()[0].symbol_mode() {SymbolMode::Var(SymbolDepth::Depth(2)) => true, _ => false});
+        assert_eq!(children[0].lambda_clauses().unwrap().len(), 1);
+        let tree = lambda.to_string_tree();
+        let sections: Vec<&str> = tree.lines().map(|l| l.trim()).filter(|l| l.starts_with('*')).collect();
+        assert_eq!(sections, vec!["*Name", "*Clauses", "*Parameters", "*Parameters", "*Parameters", "*Rest"]);
+
+        let err = parse("(fn ([x] 1) ([y] 2))", &module_info, &lb, &zone_allocator).err().unwrap();
+        assert_eq!(err.to_string(), "Duplicate arity clause.:test:13:1");
+        for code in &["(fn ([& x] 1) ([& y] 2))", "(fn ([x & y] 1) ([a b c] 2))", "(fn f 1)", "(fn ([x] 1) [y])", "(fn a/b [x] x)"] {
+            assert!(parse(code, &module_info, &lb, &zone_allocator).is_err());
+        }
+    }
+
+    #[test]
+    fn test_defn() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let code = "(defn f \"Doc.\" {:added \"1.0\"} [x] (f x)) (defn- g ([] 1) ([x] x)) (def ^:dynamic h \"Doc of h.\" 1) (def i)\n\
+                    (defmacro m \"Doc of m.\" [x] x)";
+        let module = parse(code, &module_info, &lb, &zone_allocator).ok().unwrap();
+        let children = module.children().unwrap();
+
+        let f = children[0];
+        assert_eq!(sexp(f.def_name().unwrap()), "f");
+        assert_eq!(f.docstring(), Some("Doc."));
+        assert_eq!(f.meta_value(":added").unwrap().string_value(), Some("1.0"));
+        assert!(!f.is_private());
+        let lambda = f.def_expr().unwrap();
+        assert_eq!(lambda.arity(), Some((1, false)));
+        let call = lambda.lambda_body().unwrap()[0];
+        assert!(match call.children().unwrap()[0].symbol_mode() {SymbolMode::Var(_) => true, _ => false});
+
+        let g = children[1];
+        assert!(g.is_private());
+        assert_eq!(g.docstring(), None);
+        assert_eq!(g.def_expr().unwrap().lambda_clauses().unwrap().len(), 2);
+
+        let h = children[2];
+        assert_eq!(h.docstring(), Some("Doc of h."));
+        assert_eq!(h.meta_value(":dynamic").unwrap().boolean_value(), Some(true));
+        assert_eq!(h.def_expr().unwrap().int_value(), Some(1));
+
+        assert!(children[3].def_expr().is_none());
+        assert_eq!(children[4].docstring(), Some("Doc of m."));
+
+        for code in &["(def x 1 2)", "(def x {:a 1} \"doc\" 1)", "(defn f)", "(defn f \"doc\")", "(defn fn [x] x)"] {
+            assert!(parse(code, &module_info, &lb, &zone_allocator).is_err());
+        }
+    }
+
+    #[test]
+    fn test_ns() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let code = "(ns my.app.core \"Doc.\"\n\
+                    (:require [clojure.string :as str] my.app.db [my.app.util :refer [f g]] [my.app.all :refer :all]\n\
+                              (my.lib [a :as la] b))\n\
+                    (:import [java.util Timer TimerTask] java.net.URL))\n\
+                    `x";
+        let module = parse(code, &module_info, &lb, &zone_allocator).ok().unwrap();
+        let children = module.children().unwrap();
+        let ns = children[0];
+        assert_eq!(sexp(ns.ns_name().unwrap()), "my.app.core");
+        assert_eq!(ns.docstring(), Some("Doc."));
+        assert_eq!(ns.ns_references().unwrap().len(), 2);
+        assert_eq!(sexp(children[1]), "(quote my.app.core/x)");
+
+        assert_eq!(module_info.namespace(), Some("my.app.core".to_string()));
+        assert_eq!(module_info.dependencies(), vec!["clojure.string", "my.app.db", "my.app.util", "my.app.all", "my.lib.a", "my.lib.b"]);
+        assert_eq!(module_info.resolve_alias("str"), Some("clojure.string".to_string()));
+        assert_eq!(module_info.resolve_alias("la"), Some("my.lib.a".to_string()));
+        assert_eq!(module_info.requires()[2].refer(), &Refer::Names(vec!["f".to_string(), "g".to_string()]));
+        assert_eq!(module_info.referred_namespace("g", &[]), Some("my.app.util".to_string()));
+        assert_eq!(module_info.referred_namespace("h", &[]), None);
+        let all_info = ModuleInfo::new("all");
+        all_info.set_namespace("my.app.all");
+        all_info.add_var("h");
+        assert_eq!(module_info.referred_namespace("h", &[&all_info]), Some("my.app.all".to_string()));
+        all_info.add_private_var("h");
+        assert_eq!(module_info.referred_namespace("h", &[&all_info]), None);
+        assert_eq!(module_info.resolve_class("TimerTask"), Some("java.util.TimerTask".to_string()));
+        assert_eq!(module_info.resolve_class("URL"), Some("java.net.URL".to_string()));
+        assert!(parse("(ns again)", &module_info, &lb, &zone_allocator).is_err());
+
+        for code in &["(ns a/b)", "(ns a (:require [b :as]))", "(ns a (:require [b :rename {}]))", "(ns a (:use b))",
+                      "(ns a (:import URL))", "(ns a [:require b])"] {
+            let zone_allocator = ZoneAllocator::new();
+            let module_info = ModuleInfo::new("test");
+            let lb = LiteralBuffer::new(&zone_allocator);
+            assert!(parse(code, &module_info, &lb, &zone_allocator).is_err());
+        }
+    }
+
+    #[test]
+    fn test_qualified_symbol() {
+        let zone_allocator = ZoneAllocator::new();
+        let util_info = ModuleInfo::new("util");
+        let module_info = ModuleInfo::new("test");
+        let all_info = ModuleInfo::new("test");
+        let refer_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let util = Parser::new_from_code(&util_info, "(ns my.util) (defn- secret [] 1) (def ^:private hidden 2) (defn f [] (secret))", &lb, &zone_allocator);
+        assert!(util.parse().is_ok());
+        assert!(util_info.is_private_var("secret"));
+        assert!(util_info.is_private_var("hidden"));
+        assert!(!util_info.is_private_var("f"));
+
+        let code = "(ns my.core (:require [my.util :as u :refer [f]] [clojure.string :as str]) (:import [java.util Date]))\n\
+                    (u/f) (my.util/f) (str/join xs) (my.core/g) (f) (let [f 1] f) (Date/from x) (System/exit 0) #'u/secret (java.io.File/createTempFile)\n\
+                    ::k ::str/k :u/k";
+        let parser = Parser::new_from_code(&module_info, code, &lb, &zone_allocator);
+        parser.add_module(&util_info);
+        let module = parser.parse().ok().unwrap();
+        let children = module.children().unwrap();
+        let heads: Vec<Option<(&str, &str)>> = children[1..11].iter().map(|c| match c {
+            &Ast::List(_) => c.children().unwrap()[0].qualified_name(),
+            _ => None
+        }).collect();
+        assert_eq!(heads, vec![Some(("my.util", "f")), Some(("my.util", "f")), Some(("clojure.string", "join")), Some(("my.core", "g")),
+                               Some(("my.util", "f")), None, Some(("java.util.Date", "from")), Some(("java.lang.System", "exit")), None,
+                               Some(("java.io.File", "createTempFile"))]);
+        assert!(children[6].let_body()[0].qualified_name().is_none());
+        assert_eq!(children[9].children().unwrap()[1].qualified_name(), Some(("my.util", "secret")));
+        let keywords: Vec<&str> = children[11..].iter().map(|k| k.string_value().unwrap()).collect();
+        assert_eq!(keywords, vec![":my.core/k", ":clojure.string/k", ":u/k"]);
+
+        for &(code, message) in &[("(u/secret)", "var: my.util/secret is private.:test:2:1"), ("my.util/hidden", "var: my.util/hidden is private.:test:1:1"),
+                                  ("(nothing/f)", "No such namespace: nothing.:test:2:1"), ("::nothing/k", "No such namespace: nothing.:test:1:1"),
+                                  ("(Foo/x)", "No such namespace: Foo.:test:2:1")] {
+            let parser = Parser::new_from_code(&module_info, code, &lb, &zone_allocator);
+            parser.add_module(&util_info);
+            let err = parser.parse().err().unwrap();
+            assert_eq!(err.to_string(), message);
+        }
+
+        let parser = Parser::new_from_code(&all_info, "(ns my.all (:require [my.util :refer :all])) (f) (secret) (g)", &lb, &zone_allocator);
+        parser.add_module(&util_info);
+        let module = parser.parse().ok().unwrap();
+        let heads: Vec<Option<(&str, &str)>> = module.children().unwrap()[1..].iter().map(|c| c.children().unwrap()[0].qualified_name()).collect();
+        assert_eq!(heads, vec![Some(("my.util", "f")), None, None]);
+
+        let parser = Parser::new_from_code(&refer_info, "(ns my.refer (:require [my.util :refer [secret]])) (secret)", &lb, &zone_allocator);
+        parser.add_module(&util_info);
+        assert_eq!(parser.parse().err().unwrap().to_string(), "var: my.util/secret is private.:test:53:1");
+    }
+
+    #[test]
+    fn test_form_span() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let parser = Parser::new_from_code(&module_info, "(def x\n  [1 '(2)])", &lb, &zone_allocator);
+        let module = parser.parse().ok().unwrap();
+        let def = module.children().unwrap()[0];
+        let info = def.source_info().unwrap();
+        assert_eq!((info.start(), info.end()), (0, 18));
+        assert_eq!((info.line(), info.pos(), info.end_line(), info.end_pos()), (1, 1, 2, 12));
+        let vector = def.def_expr().unwrap();
+        let info = vector.source_info().unwrap();
+        assert_eq!((info.start(), info.end()), (9, 17));
+        let quote = vector.children().unwrap()[1];
+        let info = quote.source_info().unwrap();
+        assert_eq!((info.start(), info.end()), (12, 16));
     }
 }
@@ -3,449 +3,736 @@
 ///! Author Taketoshi Aono
 ///!
 
-use regex::{Regex, Captures};
-use std::result::{Result};
 use std::string::String;
+use std::io;
+use std::io::BufRead;
+use std::cmp;
+use std::str;
 use parser::loader;
 use parser::literal_buffer;
 use parser::token::{Token, TokenKind};
 use parser::sourceinfo::SourceInfo;
 use parser::moduleinfo::ModuleInfo;
-use std::collections::HashMap;
-use std::cell::{Cell,RefCell, RefMut};
-
-
-const REGEXP: &'static str = r#"(?x)
-              (?P<comment>;[^\n\r]*)|
-              (?P<left_paren>\()|
-              (?P<right_paren>\))|
-              (?P<left_brace>\{)|
-              (?P<right_brace>\})|
-              (?P<left_bracket>\[)|
-              (?P<right_bracket>\])|
-              (?P<short_lambda>\#\()|
-              (?P<string>(?:")(?:\\.|[^"])*(?:"))|
-              (?P<quote_rm>')|
-              (?P<quote>quote(?:\s+))|
-              (?P<let>let(?:\s+))|
-              (?P<fn>fn(?:\s+))|
-              (?P<if>if(?:\s+))|
-              (?P<def>def(?:\s+))|
-              (?P<defmacro>defmacro(?:\s+))|
-              (?P<param_name>%(?:[1-9][0-9]*|&)?)|
-              (?P<int>-?[0-9]+)|
-              (?P<float>-?(?:[0-9]+(?:\.[0-9]+)?(?:[eE]-?[0-9]+)?|Infinity|NaN))|
-              (?P<hex>0[xX][0-9a-fA-F]+)|
-              (?P<bin>0[bB][10]+)|
-              (?P<long>-?[0-9]+(?:[lL]))|
-              (?P<bign>-?[0-9]+(?:[nN]))|
-              (?P<charu>\\u[0-9D-Fd-f][0-9a-fA-F]{3})|
-              (?P<lf>[\n\r])|
-              (?P<white_spaces>[\s\t]+)|
-              (?P<regexp>\#"(?:\\.|[^"])*")|
-              (?P<nil>nil)|
-              (?P<boolean>true|false)|
-              (?P<unquote_splicing>~@)|
-              (?P<defef>@)|
-              (?P<backtick>`)|
-              (?P<unquote>~)|
-              (?P<tag>\^)|
-              (?P<dispatch>\#[^"]+)|
-              (?P<keyword>:(?:[\./]|(?:[^\^`\#'"~@:%\(\)\[\]\{\}\n\r\t\s,](?:[^\^`\#'"~@:%\(\)\[\]\{\}\n\r\t\s,]|[0-9])*)))|
-              (?P<macro_keyword>::(?:[\./]|(?:[^\^`\#'"~@:%\(\)\[\]\{\}\n\r\t\s,](?:[^\^`\#'"~@:%\(\)\[\]\{\}\n\r\t\s,]|[0-9])*)))|
-              (?P<symbol>(?:[\./]|(?:[^\^`\#'"~@:%\(\)\[\]\{\}\n\r\t\s,](?:[^\^`\#'"~@:%\(\)\[\]\{\}\n\r\t\s\s,]|[0-9])*)))|
-              (?P<any>.)
-            "#;
-
-const LF_REGEXP: &'static str = r#"(?x)
-              (?P<lf>[\n\r]) "#;
-
-
-struct ParseErr {
-    cause: String
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+
+
+/// States of the numeric literal automaton.
+#[derive(Copy, Clone, PartialEq)]
+enum NumberState {
+    Start,
+    Sign,
+    Zero,
+    Digits,
+    HexPrefix,
+    Hex,
+    BinaryPrefix,
+    Binary,
+    Dot,
+    Fraction,
+    ExponentMark,
+    ExponentSign,
+    Exponent,
+    Long,
+    BigNumber,
+    BigDecimal,
+    RadixMark,
+    Radix,
+    RatioMark,
+    Ratio,
+    Error
 }
 
 
+impl NumberState {
+    fn next(self, c: char) -> NumberState {
+        match (self, c) {
+            (NumberState::Start, '-') => NumberState::Sign,
+            (NumberState::Start, '0') |
+            (NumberState::Sign, '0') => NumberState::Zero,
+            (NumberState::Start, '1'...'9') |
+            (NumberState::Sign, '1'...'9') |
+            (NumberState::Zero, '0'...'9') |
+            (NumberState::Digits, '0'...'9') => NumberState::Digits,
+            (NumberState::Zero, 'x') |
+            (NumberState::Zero, 'X') => NumberState::HexPrefix,
+            (NumberState::HexPrefix, '0'...'9') |
+            (NumberState::HexPrefix, 'a'...'f') |
+            (NumberState::HexPrefix, 'A'...'F') |
+            (NumberState::Hex, '0'...'9') |
+            (NumberState::Hex, 'a'...'f') |
+            (NumberState::Hex, 'A'...'F') => NumberState::Hex,
+            (NumberState::Zero, 'b') |
+            (NumberState::Zero, 'B') => NumberState::BinaryPrefix,
+            (NumberState::BinaryPrefix, '0'...'1') |
+            (NumberState::Binary, '0'...'1') => NumberState::Binary,
+            (NumberState::Zero, '.') |
+            (NumberState::Digits, '.') => NumberState::Dot,
+            (NumberState::Dot, '0'...'9') |
+            (NumberState::Fraction, '0'...'9') => NumberState::Fraction,
+            (NumberState::Zero, 'e') |
+            (NumberState::Zero, 'E') |
+            (NumberState::Digits, 'e') |
+            (NumberState::Digits, 'E') |
+            (NumberState::Fraction, 'e') |
+            (NumberState::Fraction, 'E') => NumberState::ExponentMark,
+            (NumberState::ExponentMark, '+') |
+            (NumberState::ExponentMark, '-') => NumberState::ExponentSign,
+            (NumberState::ExponentMark, '0'...'9') |
+            (NumberState::ExponentSign, '0'...'9') |
+            (NumberState::Exponent, '0'...'9') => NumberState::Exponent,
+            (NumberState::Zero, 'l') |
+            (NumberState::Zero, 'L') |
+            (NumberState::Digits, 'l') |
+            (NumberState::Digits, 'L') => NumberState::Long,
+            (NumberState::Zero, 'n') |
+            (NumberState::Zero, 'N') |
+            (NumberState::Digits, 'n') |
+            (NumberState::Digits, 'N') => NumberState::BigNumber,
+            (NumberState::Zero, 'M') |
+            (NumberState::Digits, 'M') |
+            (NumberState::Fraction, 'M') |
+            (NumberState::Exponent, 'M') => NumberState::BigDecimal,
+            (NumberState::Digits, 'r') |
+            (NumberState::Digits, 'R') => NumberState::RadixMark,
+            (NumberState::RadixMark, '0'...'9') |
+            (NumberState::RadixMark, 'a'...'z') |
+            (NumberState::RadixMark, 'A'...'Z') |
+            (NumberState::Radix, '0'...'9') |
+            (NumberState::Radix, 'a'...'z') |
+            (NumberState::Radix, 'A'...'Z') => NumberState::Radix,
+            (NumberState::Zero, '/') |
+            (NumberState::Digits, '/') => NumberState::RatioMark,
+            (NumberState::RatioMark, '0'...'9') |
+            (NumberState::Ratio, '0'...'9') => NumberState::Ratio,
+            _ => NumberState::Error
+        }
+    }
+
+
+    fn token_kind(self) -> TokenKind {
+        match self {
+            NumberState::Zero |
+            NumberState::Digits => TokenKind::Int,
+            NumberState::Hex => TokenKind::Hex,
+            NumberState::Binary => TokenKind::Binary,
+            NumberState::Fraction |
+            NumberState::Exponent => TokenKind::Float,
+            NumberState::Long => TokenKind::Long,
+            NumberState::BigNumber => TokenKind::BigNumber,
+            NumberState::BigDecimal => TokenKind::BigDecimal,
+            NumberState::Radix => TokenKind::Radix,
+            NumberState::Ratio => TokenKind::Ratio,
+            _ => TokenKind::Invalid
+        }
+    }
+}
+
+
+/// Whether the char terminates a symbol, keyword or number.
+fn is_delimiter(c: char) -> bool {
+    match c {
+        '^' | '`' | '#' | '\'' | '"' | '~' | '@' | ':' | '%' |
+        '(' | ')' | '[' | ']' | '{' | '}' | ',' | ';' => true,
+        _ => c.is_whitespace()
+    }
+}
+
+
+fn is_whitespace(c: char) -> bool {
+    c == ',' || c.is_whitespace()
+}
+
+
+/// Consumed input is dropped from the buffer once it grows beyond this size.
+const COMPACTION_THRESHOLD: usize = 8192;
+
+
+/// Bytes read from the source at once.
+const READ_CHUNK_SIZE: usize = 8192;
+
+
 pub struct Scanner<'a> {
-    code: String,
+    /// Buffered input, `code[0]` is at the byte offset `offset` of the whole input.
+    code: RefCell<String>,
+    offset: Cell<usize>,
+    /// Where more input comes from, None if the whole input is already in the buffer.
+    source: RefCell<Option<Box<BufRead + 'a>>>,
+    /// Trailing bytes of an utf-8 sequence split across two reads.
+    pending: RefCell<Vec<u8>>,
+    /// The reason why reading the source failed, reported by the next token instead of the end of input.
+    read_error: RefCell<Option<String>>,
     current_pos: Cell<i32>,
     current_line: Cell<i32>,
     index: Cell<usize>,
-    len: usize,
-    r: Regex,
-    lf_r: Regex,
+    preserve_trivia: Cell<bool>,
+    incomplete: Cell<bool>,
+    /// Set while scanning a token cut by the end of input.
+    truncated: Cell<bool>,
+    /// The index, column and line where the last token cut by the end of input began,
+    /// the token is scanned again from there when more input is fed.
+    resume: Cell<Option<(usize, i32, i32)>>,
+    /// Tokens scanned ahead by `peek_nth`, with their truncated flag.
+    lookahead: RefCell<VecDeque<(Token<'a>, bool)>>,
     module_info: &'a ModuleInfo,
     literal_buffer: &'a literal_buffer::LiteralBuffer<'a>
 }
 
 
 impl<'a> Scanner<'a> {
-    pub fn new_code(module_info: &'a ModuleInfo, code: &str, lb: &'a literal_buffer::LiteralBuffer<'a>) -> Scanner<'a> {
+    fn new(module_info: &'a ModuleInfo, code: String, source: Option<Box<BufRead + 'a>>,
+           lb: &'a literal_buffer::LiteralBuffer<'a>) -> Scanner<'a> {
         Scanner {
-            code: code.to_string(),
+            code: RefCell::new(code),
+            offset: Cell::new(0),
+            source: RefCell::new(source),
+            pending: RefCell::new(Vec::new()),
+            read_error: RefCell::new(None),
             current_pos: Cell::new(1),
             current_line: Cell::new(1),
             index: Cell::new(0),
-            len: code.bytes().count(),
+            preserve_trivia: Cell::new(false),
+            incomplete: Cell::new(false),
+            truncated: Cell::new(false),
+            resume: Cell::new(None),
+            lookahead: RefCell::new(VecDeque::new()),
             literal_buffer: lb,
-            module_info: module_info,
-            r: Regex::new(REGEXP).unwrap(),
-            lf_r: Regex::new(LF_REGEXP).unwrap()
+            module_info: module_info
         }
     }
 
 
-    pub fn new_file(module_info: &'a ModuleInfo, lb: &'a literal_buffer::LiteralBuffer<'a>) -> Scanner<'a> {
-        let code = loader::load(module_info.filename());
-        let len = code.bytes().count();
-        Scanner {
-            code: code,
-            current_pos: Cell::new(1),
-            current_line: Cell::new(1),
-            index: Cell::new(0),
-            len: len,
-            literal_buffer: lb,
-            module_info: module_info,
-            r: Regex::new(REGEXP).unwrap(),
-            lf_r: Regex::new(LF_REGEXP).unwrap()
+    pub fn new_code(module_info: &'a ModuleInfo, code: &str, lb: &'a literal_buffer::LiteralBuffer<'a>) -> Scanner<'a> {
+        Scanner::new(module_info, code.to_string(), None, lb)
+    }
+
+
+    pub fn new_file(module_info: &'a ModuleInfo, lb: &'a literal_buffer::LiteralBuffer<'a>) -> Result<Scanner<'a>, loader::LoadError> {
+        match loader::open(module_info.filename()) {
+            Ok(reader) => Ok(Scanner::new_reader(module_info, Box::new(reader), lb)),
+            Err(e) => Err(e)
         }
     }
 
 
+    /// Scan the input pulled from `reader` chunk by chunk, so stdin, sockets
+    /// and files larger than memory can be read without loading them at once.
+    pub fn new_reader(module_info: &'a ModuleInfo, reader: Box<BufRead + 'a>,
+                      lb: &'a literal_buffer::LiteralBuffer<'a>) -> Scanner<'a> {
+        Scanner::new(module_info, String::new(), Some(reader), lb)
+    }
+
+
+    /// Append more input, e.g. the next line typed into the REPL.
+    /// The token cut by the end of input, such as `"ab` of an unterminated string,
+    /// is scanned again with the fed input even if it was already consumed.
+    pub fn feed(&self, code: &str) {
+        self.code.borrow_mut().push_str(code);
+        self.lookahead.borrow_mut().retain(|&(token, truncated)| !truncated && !token.is(TokenKind::Eof));
+        match self.resume.get() {
+            Some((index, pos, line)) => {
+                self.index.set(index);
+                self.current_pos.set(pos);
+                self.current_line.set(line);
+                self.resume.set(None);
+                self.incomplete.set(false);
+            }
+            None => {}
+        }
+    }
+
+
+    /// True if the last scanned token was cut by the end of input,
+    /// such as an unterminated string. Feeding more input may complete it.
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete.get()
+    }
+
+
+    /// Keep whitespaces and comments around tokens instead of discarding them.
+    /// See `Token::raw`, `Token::leading_trivia` and `Token::trailing_trivia`.
+    pub fn set_preserve_trivia(&self, preserve: bool) {
+        self.preserve_trivia.set(preserve);
+    }
+
+
+    /// Consume the next token, Eof is returned repeatedly at the end of input.
     pub fn scan(&self) -> Token<'a> {
+        self.peek();
+        let (token, truncated) = self.lookahead.borrow_mut().pop_front().unwrap();
+        self.incomplete.set(truncated);
+        token
+    }
+
+
+    /// The next token without consuming it.
+    pub fn peek(&self) -> Token<'a> {
+        self.peek_nth(0)
+    }
+
+
+    /// The token `n` tokens ahead without consuming anything, `peek_nth(0)` is `peek()`.
+    pub fn peek_nth(&self, n: usize) -> Token<'a> {
         loop {
-            let cap = self.r.captures(&self.code[self.index.get()..]);
-            match cap {
-                Some(cap) => {
-                    let matched = self.do_match(&cap);
-                    let len = cap.at(0).unwrap().len();
-//                    println!("{}", cap.at(0).unwrap());
-                    self.index.set(self.index.get() + len);
-                    self.current_pos.set(self.current_pos.get() + len as i32);
-                    match matched {
-                        Option::None => {
-                            continue;
-                        }
-                        Option::Some(token) => {
-                            return token;
-                        }
-                    }
-                }
-                None => {
-                    return Token::new_eof(self.make_info());
-                }
+            if let Some(&(token, _)) = self.lookahead.borrow().get(n) {
+                return token;
             }
+            let token = self.scan_next();
+            self.lookahead.borrow_mut().push_back((token, self.truncated.get()));
         }
     }
 
-    fn do_match(&self, cap: &Captures) -> Option<Token<'a>> {
-        match cap.name("lf") {
-            Some(t) => {
-                self.inc_line_number();
-                self.current_pos.set(1);
-                return Option::None;
-            }
-            None => {}
-        };
 
-        match cap.name("comment") {
-            Some(t) => {
-                // let value_id = self.literal_buffer.get(t);
-                // return Token::new_value(self.make_info(), value_id, TokenKind::Comment);
-                self.count_lf(t);
-                return Option::None;
-            }
-            None => {}
-        };
+    fn scan_next(&self) -> Token<'a> {
+        self.truncated.set(false);
+        self.compact();
+        let trivia_start = self.index.get();
+        let start = (trivia_start, self.current_pos.get(), self.current_line.get());
+        loop {
+            let c = match self.peek_char() {
+                Some(c) => c,
+                None => {
+                    let info = self.make_info();
+                    let token = match self.read_error.borrow_mut().take() {
+                        Some(message) => self.invalid(info, &message),
+                        None => Token::new_eof(info)
+                    };
+                    return self.attach_trivia(token, trivia_start);
+                }
+            };
 
-        match cap.name("white_spaces") {
-            Some(t) => {
-                return Option::None;
-            }
-            None => {}
-        };
-        
-        match cap.name("left_paren") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::LeftParen));
+            if is_whitespace(c) {
+                self.advance();
+                continue;
             }
-            None => {}
-        };
 
-        match cap.name("right_paren") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::RightParen));
+            if c == ';' {
+                self.skip_comment();
+                continue;
             }
-            None => {}
-        };
 
-        match cap.name("left_brace") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::LeftBrace));
+            let info = self.make_info();
+            let token = self.scan_token(c, info).extend(self.make_info());
+            if self.truncated.get() {
+                self.resume.set(Some(start));
             }
-            None => {}
-        };
+            return self.attach_trivia(token, trivia_start);
+        }
+    }
 
-        match cap.name("right_brace") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::RightBrace));
-            }
-            None => {}
-        };
 
-        match cap.name("left_bracket") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::LeftBracket));
-            }
-            None => {}
-        };
+    /// Collect the trivia in front of the token and the trivia following it
+    /// until the end of the line.
+    fn attach_trivia(&self, token: Token<'a>, trivia_start: usize) -> Token<'a> {
+        if !self.preserve_trivia.get() {
+            return token;
+        }
 
-        match cap.name("right_bracket") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::RightBracket));
-            }
-            None => {}
-        };
+        let info = token.info();
+        let leading = self.intern_range(trivia_start, info.start());
+        let raw = self.intern_range(info.start(), info.end());
+        let trailing_start = self.index.get();
 
-        match cap.name("int") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::Int));
+        if !token.is(TokenKind::Eof) {
+            self.skip_while(|c| is_whitespace(c) && c != '\n' && c != '\r');
+            if self.peek_char() == Some(';') {
+                self.skip_comment();
             }
-            None => {}
-        };
-
-        match cap.name("float") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::Float));
+            if self.peek_char() == Some('\r') {
+                self.advance();
             }
-            None => {}
-        };
-
-        match cap.name("hex") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::Hex));
+            if self.peek_char() == Some('\n') {
+                self.advance();
             }
-            None => {}
-        };
+        }
 
-        match cap.name("bin") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::Binary));
-            }
-            None => {}
-        };
+        let trailing = self.intern_range(trailing_start, self.index.get());
+        token.with_trivia(raw, leading, trailing)
+    }
 
-        match cap.name("long") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::Long));
-            }
-            None => {}
-        };
 
-        match cap.name("bign") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::BigNumber));
-            }
-            None => {}
-        };
+    fn intern_range(&self, start: usize, end: usize) -> i64 {
+        if start == end {
+            return -1;
+        }
+        self.intern(start, end)
+    }
 
-        match cap.name("charu") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::UnicodeChar));
-            }
-            None => {}
-        };
 
-        match cap.name("string") {
-            Some(t) => {
-                unsafe {
-                    let value_id = self.literal_buffer.get(t.slice_unchecked(1, t.len() - 1));
-                    self.count_lf(t);
-                    return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::String));
+    /// Intern the input between the byte offsets `start` and `end`.
+    fn intern(&self, start: usize, end: usize) -> i64 {
+        let offset = self.offset.get();
+        self.literal_buffer.get(&self.code.borrow()[start - offset..end - offset])
+    }
+
+
+    fn scan_token(&self, c: char, info: SourceInfo<'a>) -> Token<'a> {
+        match c {
+            '(' => self.single(info, TokenKind::LeftParen),
+            ')' => self.single(info, TokenKind::RightParen),
+            '[' => self.single(info, TokenKind::LeftBracket),
+            ']' => self.single(info, TokenKind::RightBracket),
+            '{' => self.single(info, TokenKind::LeftBrace),
+            '}' => self.single(info, TokenKind::RightBrace),
+            '\'' => self.single(info, TokenKind::QuoteRm),
+            '`' => self.single(info, TokenKind::Backtick),
+            '@' => self.single(info, TokenKind::Deref),
+            '^' => self.single(info, TokenKind::Tag),
+            '~' => {
+                self.advance();
+                if self.peek_char() == Some('@') {
+                    self.advance();
+                    return Token::new(info, TokenKind::UnquoteSplicing);
+                }
+                Token::new(info, TokenKind::Unquote)
+            }
+            '"' => self.scan_string(info),
+            '#' => self.scan_dispatch(info),
+            '%' => self.scan_param_name(info),
+            ':' => self.scan_keyword(info),
+            '\\' => self.scan_char(info),
+            '0'...'9' => self.scan_number(info),
+            '-' => {
+                match self.peek_char_at(1) {
+                    Some('0'...'9') => self.scan_number(info),
+                    _ => self.scan_symbol(info)
                 }
             }
-            None => {}
-        };
+            _ => self.scan_symbol(info)
+        }
+    }
 
-        match cap.name("regexp") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::Regexp));
-            }
-            None => {}
-        };
-        
-        match cap.name("nil") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::Nil));
-            }
-            None => {}
-        };
 
-        match cap.name("boolean") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::Boolean));
-            }
-            None => {}
-        };
+    /// Invalid token which holds the reason as its value.
+    fn invalid(&self, info: SourceInfo<'a>, message: &str) -> Token<'a> {
+        Token::new_value(info, self.literal_buffer.get(message), TokenKind::Invalid)
+    }
 
-        match cap.name("unquote_splicing") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::UnquoteSplicing));
-            }
-            None => {}
-        };
 
-        match cap.name("deref") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::Deref));
-            }
+    /// Token cut by the end of input, or by the failure of reading the source
+    /// which more input cannot complete.
+    fn unterminated(&self, info: SourceInfo<'a>, message: &str) -> Token<'a> {
+        match self.read_error.borrow_mut().take() {
+            Some(error) => {return self.invalid(info, &error);}
             None => {}
-        };
+        }
+        self.truncated.set(true);
+        self.invalid(info, message)
+    }
 
-        match cap.name("quote_rm") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::QuoteRm));
-            }
-            None => {}
-        };
 
-        match cap.name("quote") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::Quote));
-            }
-            None => {}
-        };
+    fn single(&self, info: SourceInfo<'a>, kind: TokenKind) -> Token<'a> {
+        self.advance();
+        Token::new(info, kind)
+    }
 
-        match cap.name("backtick") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::Backtick));
-            }
-            None => {}
-        };
 
-        match cap.name("unquote") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::Unquote));
+    fn skip_comment(&self) {
+        loop {
+            match self.peek_char() {
+                Some('\n') | Some('\r') | None => {
+                    return;
+                }
+                _ => {
+                    self.advance();
+                }
             }
-            None => {}
-        };
+        }
+    }
 
-        match cap.name("tag") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::Tag));
-            }
-            None => {}
-        };
 
-        match cap.name("dispatch") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::String));
+    fn scan_string(&self, info: SourceInfo<'a>) -> Token<'a> {
+        self.advance();
+        let start = self.index.get();
+        loop {
+            match self.advance() {
+                Some('"') => {
+                    let value_id = self.intern(start, self.index.get() - 1);
+                    return Token::new_value(info, value_id, TokenKind::String);
+                }
+                Some('\\') => {
+                    self.advance();
+                }
+                Some(_) => {}
+                None => {
+                    return self.unterminated(info, "Unterminated string literal.");
+                }
             }
-            None => {}
-        };
+        }
+    }
 
-        match cap.name("keyword") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::Keyword));
-            }
-            None => {}
-        };
 
-        match cap.name("macro_keyword") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::MacroKeyword));
+    fn scan_dispatch(&self, info: SourceInfo<'a>) -> Token<'a> {
+        let start = self.index.get();
+        self.advance();
+        match self.peek_char() {
+            Some('(') => self.single(info, TokenKind::ShortLambdaBegin),
+            Some('{') => self.single(info, TokenKind::SetBegin),
+            Some('_') => self.single(info, TokenKind::Discard),
+            Some('\'') => self.single(info, TokenKind::VarQuote),
+            Some('?') => {
+                self.advance();
+                if self.peek_char() == Some('@') {
+                    return self.single(info, TokenKind::ReaderConditionalSplicing);
+                }
+                Token::new(info, TokenKind::ReaderConditional)
+            }
+            Some('"') => {
+                self.advance();
+                loop {
+                    match self.advance() {
+                        Some('"') => {
+                            let value_id = self.intern(start, self.index.get());
+                            return Token::new_value(info, value_id, TokenKind::Regexp);
+                        }
+                        Some('\\') => {
+                            self.advance();
+                        }
+                        Some(_) => {}
+                        None => {
+                            return self.unterminated(info, "Unterminated regular expression literal.");
+                        }
+                    }
+                }
             }
-            None => {}
-        };
+            _ => {
+                // Tagged literal such as #inst, the value is the tag without #.
+                let tag_start = self.index.get();
+                if self.skip_while(|c| !is_delimiter(c) || c == '#' || c == '\'') == 0 {
+                    return self.invalid(info, "Unsupported dispatch macro.");
+                }
+                let value_id = self.intern(tag_start, self.index.get());
+                Token::new_value(info, value_id, TokenKind::TaggedLiteral)
+            }
+        }
+    }
 
-        match cap.name("short_lambda") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::ShortLambdaBegin));
-            },
-            None => {}
+
+    fn scan_param_name(&self, info: SourceInfo<'a>) -> Token<'a> {
+        let start = self.index.get();
+        self.advance();
+        if self.peek_char() == Some('&') {
+            self.advance();
+        } else {
+            self.skip_while(|c| c.is_digit(10));
         }
+        let value_id = self.intern(start, self.index.get());
+        Token::new_value(info, value_id, TokenKind::ParamName)
+    }
 
-        match cap.name("quote") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::Quote));
-            },
-            None => {}
+
+    fn scan_keyword(&self, info: SourceInfo<'a>) -> Token<'a> {
+        let start = self.index.get();
+        let mut kind = TokenKind::Keyword;
+        self.advance();
+        if self.peek_char() == Some(':') {
+            self.advance();
+            kind = TokenKind::MacroKeyword;
         }
+        if self.skip_while(|c| !is_delimiter(c)) == 0 {
+            return self.invalid(info, "Invalid keyword.");
+        }
+        let value_id = self.intern(start, self.index.get());
+        Token::new_value(info, value_id, kind)
+    }
 
-        match cap.name("if") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::If));
-            },
-            None => {}
+
+    /// Scan the character literal, the first char after the backslash is always
+    /// a part of the literal so that \( or \; can be written.
+    /// The name is validated by the parser.
+    fn scan_char(&self, info: SourceInfo<'a>) -> Token<'a> {
+        let start = self.index.get();
+        self.advance();
+        if self.advance().is_none() {
+            return self.invalid(info, "Invalid character literal.");
         }
+        self.skip_while(|c| !is_delimiter(c));
+        let value_id = self.intern(start, self.index.get());
+        Token::new_value(info, value_id, TokenKind::UnicodeChar)
+    }
 
-        match cap.name("fn") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::Lambda));
-            },
-            None => {}
+
+    fn scan_number(&self, info: SourceInfo<'a>) -> Token<'a> {
+        let start = self.index.get();
+        let mut state = NumberState::Start;
+        loop {
+            match self.peek_char() {
+                Some(c) if !is_delimiter(c) => {
+                    state = state.next(c);
+                    self.advance();
+                }
+                _ => break
+            }
+        }
+        let kind = state.token_kind();
+        if kind == TokenKind::Invalid {
+            return self.invalid(info, "Invalid number literal.");
         }
+        let value_id = self.intern(start, self.index.get());
+        Token::new_value(info, value_id, kind)
+    }
 
-        match cap.name("let") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::Let));
-            },
-            None => {}
+
+    fn scan_symbol(&self, info: SourceInfo<'a>) -> Token<'a> {
+        let start = self.index.get();
+        if self.skip_while(|c| !is_delimiter(c)) == 0 {
+            self.advance();
+            return self.invalid(info, "Unexpected character.");
         }
+        // # and ' are allowed after the first char, as in the auto-gensym x#.
+        self.skip_while(|c| !is_delimiter(c) || c == '#' || c == '\'');
+        let value_id = self.intern(start, self.index.get());
+        // Special forms such as `if` or `fn` are symbols too, the parser recognizes them.
+        let kind = match self.literal_buffer.find(value_id) {
+            "nil" => TokenKind::Nil,
+            "true" | "false" => TokenKind::Boolean,
+            "Infinity" | "NaN" | "-Infinity" | "-NaN" => TokenKind::Float,
+            _ => TokenKind::Symbol
+        };
+        Token::new_value(info, value_id, kind)
+    }
 
-        match cap.name("def") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::Def));
-            },
-            None => {}
+
+    fn peek_char(&self) -> Option<char> {
+        self.peek_char_at(0)
+    }
+
+
+    fn peek_char_at(&self, n: usize) -> Option<char> {
+        loop {
+            let c = self.code.borrow()[self.index.get() - self.offset.get()..].chars().nth(n);
+            if c.is_some() || !self.fill() {
+                return c;
+            }
         }
+    }
 
 
-        match cap.name("defmacro") {
-            Some(t) => {
-                return Option::Some(Token::new(self.make_info(), TokenKind::DefMacro));
-            },
-            None => {}
+    /// Read the next chunk from the source into the buffer,
+    /// return false if there is nothing left to read.
+    fn fill(&self) -> bool {
+        let mut source = self.source.borrow_mut();
+        let read = match *source {
+            Some(ref mut reader) => {
+                let chunk = loop {
+                    match reader.fill_buf() {
+                        Ok(buf) => break buf[..cmp::min(buf.len(), READ_CHUNK_SIZE)].to_vec(),
+                        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                        Err(e) => {
+                            *self.read_error.borrow_mut() = Some(format!("Could not read the input: {}.", e));
+                            break Vec::new();
+                        }
+                    }
+                };
+                reader.consume(chunk.len());
+                if chunk.is_empty() {
+                    false
+                } else {
+                    self.append_bytes(chunk);
+                    true
+                }
+            }
+            None => false
+        };
+        if !read {
+            // Nothing will complete a split utf-8 sequence anymore.
+            let rest: Vec<u8> = self.pending.borrow_mut().drain(..).collect();
+            if !rest.is_empty() {
+                self.code.borrow_mut().push_str(&String::from_utf8_lossy(&rest));
+                return true;
+            }
+            *source = None;
         }
+        read
+    }
 
 
-        match cap.name("symbol") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::Symbol));
+    fn append_bytes(&self, chunk: Vec<u8>) {
+        let mut pending = self.pending.borrow_mut();
+        pending.extend(chunk);
+        let mut code = self.code.borrow_mut();
+        loop {
+            let (valid, invalid) = match str::from_utf8(&pending) {
+                Ok(s) => {
+                    code.push_str(s);
+                    (pending.len(), 0)
+                }
+                Err(e) => {
+                    let valid = e.valid_up_to();
+                    code.push_str(unsafe {str::from_utf8_unchecked(&pending[..valid])});
+                    match e.error_len() {
+                        Some(len) => {
+                            code.push('\u{FFFD}');
+                            (valid, len)
+                        }
+                        // Incomplete sequence at the end, wait for the next chunk.
+                        None => (valid, 0)
+                    }
+                }
+            };
+            pending.drain(..valid + invalid);
+            if invalid == 0 {
+                return;
             }
-            None => {}
+        }
+    }
+
+
+    /// Drop the consumed input so that the buffer does not grow with the whole input.
+    fn compact(&self) {
+        let keep = match self.resume.get() {
+            Some((index, _, _)) => cmp::min(index, self.index.get()),
+            None => self.index.get()
         };
+        let consumed = keep - self.offset.get();
+        if consumed < COMPACTION_THRESHOLD {
+            return;
+        }
+        self.code.borrow_mut().drain(..consumed);
+        self.offset.set(keep);
+    }
+
 
-        match cap.name("param_name") {
-            Some(t) => {
-                let value_id = self.literal_buffer.get(t);
-                return Option::Some(Token::new_value(self.make_info(), value_id, TokenKind::ParamName));
+    /// Consume one char, keeping the line and column counters in sync.
+    fn advance(&self) -> Option<char> {
+        let c = self.peek_char();
+        match c {
+            Some(ch) => {
+                self.index.set(self.index.get() + ch.len_utf8());
+                if ch == '\n' {
+                    self.inc_line_number();
+                    self.current_pos.set(1);
+                } else {
+                    self.current_pos.set(self.current_pos.get() + 1);
+                }
             }
             None => {}
-        };
-        
-        return Option::Some(Token::new_invalid(self.make_info()));
+        }
+        c
+    }
+
+
+    /// Consume chars while `pred` holds and return how many were consumed.
+    fn skip_while<F>(&self, pred: F) -> usize where F: Fn(char) -> bool {
+        let mut count = 0;
+        loop {
+            match self.peek_char() {
+                Some(c) if pred(c) => {
+                    self.advance();
+                    count += 1;
+                }
+                _ => {
+                    return count;
+                }
+            }
+        }
     }
-    
-    
+
+
     fn inc_line_number(&self) {
         self.current_line.set(self.current_line.get() + 1);
     }
-    
+
 
     fn get_line_number(&self) -> i32 {
         self.current_line.get()
@@ -453,31 +740,245 @@ impl<'a> Scanner<'a> {
 
 
     fn make_info(&self) -> SourceInfo<'a> {
-        SourceInfo::new(self.current_pos.get(), self.get_line_number(), self.module_info)
+        SourceInfo::new(self.index.get(), self.current_pos.get(), self.get_line_number(), self.module_info)
     }
-    
+}
 
-    fn count_lf(&self, s: &str) {
-        for c in self.lf_r.captures_iter(s) {
-            match c.name("lf") {
-                Some(t) => self.inc_line_number(),
-                None => break
-            }
+
+/// Iterate over the tokens until the end of input, Eof is not yielded.
+/// Implemented on the reference since the scanner is shared with the parser,
+/// and to keep `Scanner::scan` from being shadowed by `Iterator::scan`.
+impl<'a, 'b> Iterator for &'b Scanner<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = Scanner::scan(self);
+        if token.is(TokenKind::Eof) {
+            return None;
         }
+        Some(token)
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     #[test]
-//     fn test_scan_1() {
-//         use super::*;
-//         let scanner = Scanner::new(r#"
-//           (defn test []
-//             (+ 1 1))
-//         "#);
-//         scanner.scan(|token: Token| {
-//             println!("{}", token);
-//         });
-//     }
-// }
+
+/// Split the code into tokens, whitespaces and comments are kept as trivia
+/// of the tokens so that highlighters can reprint the whole code.
+pub fn tokenize<'a>(module_info: &'a ModuleInfo, code: &str, lb: &'a literal_buffer::LiteralBuffer<'a>) -> Vec<Token<'a>> {
+    let scanner = Scanner::new_code(module_info, code, lb);
+    scanner.set_preserve_trivia(true);
+    let tokens = (&scanner).collect();
+    tokens
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use parser::literal_buffer::LiteralBuffer;
+    use parser::moduleinfo::ModuleInfo;
+    use parser::token::TokenKind;
+    use internal::heap::zone::ZoneAllocator;
+    use std::io::{BufReader, Read};
+
+    fn kinds(code: &str) -> Vec<TokenKind> {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let scanner = Scanner::new_code(&module_info, code, &lb);
+        let mut ret = Vec::new();
+        loop {
+            let token = scanner.scan();
+            if token.is(TokenKind::Eof) {
+                return ret;
+            }
+            ret.push(token.kind());
+        }
+    }
+
+    #[test]
+    fn test_scan_form() {
+        assert_eq!(kinds("(def x [1 \"a\" :k]) ; comment\n{nil true}"),
+                   vec![TokenKind::LeftParen, TokenKind::Symbol, TokenKind::Symbol, TokenKind::LeftBracket,
+                        TokenKind::Int, TokenKind::String, TokenKind::Keyword, TokenKind::RightBracket,
+                        TokenKind::RightParen, TokenKind::LeftBrace, TokenKind::Nil, TokenKind::Boolean,
+                        TokenKind::RightBrace]);
+        assert_eq!(kinds("x# a'b"), vec![TokenKind::Symbol, TokenKind::Symbol]);
+    }
+
+    #[test]
+    fn test_scan_numbers() {
+        assert_eq!(kinds("1 -2 1.5 1e10 0xFF 0b101 10L 10N -x 1a 36rZZ 1/3 1.5M 2r 1/"),
+                   vec![TokenKind::Int, TokenKind::Int, TokenKind::Float, TokenKind::Float,
+                        TokenKind::Hex, TokenKind::Binary, TokenKind::Long, TokenKind::BigNumber,
+                        TokenKind::Symbol, TokenKind::Invalid, TokenKind::Radix, TokenKind::Ratio,
+                        TokenKind::BigDecimal, TokenKind::Invalid, TokenKind::Invalid]);
+        assert_eq!(kinds("1e+5 1.5E-3 -2e+1 1e+ Infinity -Infinity NaN -NaN -Inf"),
+                   vec![TokenKind::Float, TokenKind::Float, TokenKind::Float, TokenKind::Invalid,
+                        TokenKind::Float, TokenKind::Float, TokenKind::Float, TokenKind::Float, TokenKind::Symbol]);
+    }
+
+    #[test]
+    fn test_scan_dispatch() {
+        assert_eq!(kinds("#inst \"\" #my/tag [] #"), vec![TokenKind::TaggedLiteral, TokenKind::String,
+                   TokenKind::TaggedLiteral, TokenKind::LeftBracket, TokenKind::RightBracket, TokenKind::Invalid]);
+        assert_eq!(kinds("#{} #_ #'a #?(#?@( #(% %2 %&) #\"a\\\"\""),
+                   vec![TokenKind::SetBegin, TokenKind::RightBrace, TokenKind::Discard, TokenKind::VarQuote,
+                        TokenKind::Symbol, TokenKind::ReaderConditional, TokenKind::LeftParen,
+                        TokenKind::ReaderConditionalSplicing, TokenKind::LeftParen,
+                        TokenKind::ShortLambdaBegin, TokenKind::ParamName, TokenKind::ParamName,
+                        TokenKind::ParamName, TokenKind::RightParen, TokenKind::Regexp]);
+    }
+
+    #[test]
+    fn test_scan_reader() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let mut code = String::new();
+        for i in 0..2000 {
+            code.push_str(&format!("(あい \"{}\")\n", i));
+        }
+        // A 1 byte buffer splits every multibyte char across reads.
+        let reader = BufReader::with_capacity(1, code.as_bytes());
+        let scanner = Scanner::new_reader(&module_info, Box::new(reader), &lb);
+        for i in 0..2000 {
+            scanner.scan();
+            assert_eq!(lb.find(scanner.scan().value()), "あい");
+            let token = scanner.scan();
+            assert_eq!(lb.find(token.value()), format!("{}", i));
+            assert_eq!((token.line(), token.info().start()), (i + 1, code.find(&format!("\"{}\"", i)).unwrap()));
+            scanner.scan();
+        }
+        assert!(scanner.scan().is(TokenKind::Eof));
+        assert!(scanner.code.borrow().len() < COMPACTION_THRESHOLD * 2);
+    }
+
+    /// Reader which fails after `data` is read.
+    struct FailingReader {
+        data: &'static [u8]
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.data.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::ConnectionReset, "connection reset"));
+            }
+            let n = cmp::min(buf.len(), self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_scan_read_error() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let scanner = Scanner::new_reader(&module_info, Box::new(BufReader::new(FailingReader {data: b"(a \"b"})), &lb);
+        scanner.scan();
+        scanner.scan();
+        let token = scanner.scan();
+        assert!(token.is(TokenKind::Invalid));
+        assert_eq!(lb.find(token.value()), "Could not read the input: connection reset.");
+        assert!(!scanner.is_incomplete());
+        assert!(scanner.scan().is(TokenKind::Eof));
+
+        let scanner = Scanner::new_reader(&module_info, Box::new(BufReader::new(FailingReader {data: b"(a "})), &lb);
+        scanner.scan();
+        scanner.scan();
+        assert!(scanner.scan().is(TokenKind::Invalid));
+        assert!(!scanner.is_incomplete());
+        assert!(scanner.scan().is(TokenKind::Eof));
+    }
+
+    #[test]
+    fn test_scan_feed() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let scanner = Scanner::new_code(&module_info, "\"ab", &lb);
+        assert!(scanner.scan().is(TokenKind::Invalid));
+        assert!(scanner.is_incomplete());
+
+        let scanner = Scanner::new_code(&module_info, "(a", &lb);
+        scanner.scan();
+        scanner.scan();
+        assert!(!scanner.is_incomplete());
+        assert!(scanner.scan().is(TokenKind::Eof));
+        scanner.feed(" b)");
+        assert!(scanner.scan().is(TokenKind::Symbol));
+        assert!(scanner.scan().is(TokenKind::RightParen));
+
+        let scanner = Scanner::new_code(&module_info, "(f \"ab", &lb);
+        scanner.scan();
+        scanner.scan();
+        assert!(scanner.scan().is(TokenKind::Invalid));
+        assert!(scanner.scan().is(TokenKind::Eof));
+        scanner.feed("c\" d)");
+        assert!(!scanner.is_incomplete());
+        let token = scanner.scan();
+        assert!(token.is(TokenKind::String));
+        assert_eq!(lb.find(token.value()), "abc");
+        assert_eq!((token.pos(), token.line()), (4, 1));
+        assert!(scanner.scan().is(TokenKind::Symbol));
+        assert!(scanner.scan().is(TokenKind::RightParen));
+        assert!(scanner.scan().is(TokenKind::Eof));
+    }
+
+    #[test]
+    fn test_lookahead() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let scanner = Scanner::new_code(&module_info, "(a \"b", &lb);
+        assert!(scanner.peek_nth(2).is(TokenKind::Invalid));
+        assert!(scanner.peek_nth(5).is(TokenKind::Eof));
+        assert!(scanner.peek().is(TokenKind::LeftParen));
+        assert!(!scanner.is_incomplete());
+        let kinds: Vec<TokenKind> = (&scanner).take(3).map(|t| t.kind()).collect();
+        assert_eq!(kinds, vec![TokenKind::LeftParen, TokenKind::Symbol, TokenKind::Invalid]);
+        assert!(scanner.is_incomplete());
+        assert!(scanner.scan().is(TokenKind::Eof));
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let tokens = tokenize(&module_info, "(f 1) ; c", &lb);
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(lb.find(tokens[1].raw()), "f");
+        assert_eq!(lb.find(tokens[3].trailing_trivia()), " ; c");
+    }
+
+    #[test]
+    fn test_scan_position() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let scanner = Scanner::new_code(&module_info, "(a\n  あい b)", &lb);
+        scanner.scan();
+        scanner.scan();
+        let token = scanner.scan();
+        assert_eq!((token.pos(), token.line()), (3, 2));
+        let token = scanner.scan();
+        assert_eq!((token.pos(), token.line()), (6, 2));
+    }
+
+    #[test]
+    fn test_scan_span() {
+        let zone_allocator = ZoneAllocator::new();
+        let module_info = ModuleInfo::new("test");
+        let lb = LiteralBuffer::new(&zone_allocator);
+        let scanner = Scanner::new_code(&module_info, "(あい \"a\nb\")", &lb);
+        scanner.scan();
+        let info = scanner.scan().info();
+        assert_eq!((info.start(), info.end()), (1, 7));
+        assert_eq!((info.pos(), info.end_pos()), (2, 4));
+        let info = scanner.scan().info();
+        assert_eq!((info.start(), info.end()), (8, 13));
+        assert_eq!((info.line(), info.end_line(), info.end_pos()), (1, 2, 3));
+    }
+}
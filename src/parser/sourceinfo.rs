@@ -6,10 +6,17 @@ use parser::moduleinfo::ModuleInfo;
 use std;
 use std::fmt::{Display, Formatter};
 
+/// Location of a token or a form in the source.
+/// `start` and `end` are byte offsets, `end` is exclusive.
+/// `pos` and `end_pos` are columns counted in chars, starting from 1.
 #[derive(Copy, Clone)]
 pub struct SourceInfo<'a> {
+    start: usize,
+    end: usize,
     pos: i32,
     line: i32,
+    end_pos: i32,
+    end_line: i32,
     module_info: &'a ModuleInfo
 }
 
@@ -22,24 +29,62 @@ impl<'a> Display for SourceInfo<'a> {
 
 
 impl<'a> SourceInfo<'a> {
-    pub fn new(pos: i32, line: i32, module_info: &'a ModuleInfo) -> SourceInfo<'a> {
+    /// Create an empty span located at `start`.
+    pub fn new(start: usize, pos: i32, line: i32, module_info: &'a ModuleInfo) -> SourceInfo<'a> {
         SourceInfo {
+            start: start,
+            end: start,
             pos: pos,
             line: line,
+            end_pos: pos,
+            end_line: line,
             module_info: module_info
         }
     }
 
+    /// Create the span which begins at self and ends at the end of `other`.
+    pub fn extend(self, other: SourceInfo<'a>) -> SourceInfo<'a> {
+        SourceInfo {
+            start: self.start,
+            end: other.end,
+            pos: self.pos,
+            line: self.line,
+            end_pos: other.end_pos,
+            end_line: other.end_line,
+            module_info: self.module_info
+        }
+    }
+
+    pub fn start(self) -> usize {
+        self.start
+    }
+
+
+    pub fn end(self) -> usize {
+        self.end
+    }
+
+
     pub fn pos(self) -> i32 {
         self.pos
     }
 
-    
+
     pub fn line(self) -> i32 {
         self.line
     }
 
-    
+
+    pub fn end_pos(self) -> i32 {
+        self.end_pos
+    }
+
+
+    pub fn end_line(self) -> i32 {
+        self.end_line
+    }
+
+
     pub fn filename(self) -> &'a str {
         self.module_info.filename()
     }
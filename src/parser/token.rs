@@ -13,12 +13,13 @@ pub enum TokenKind {
     Invalid,
     Ignore,
     Symbol,
-    Lambda,
     ShortLambdaBegin,
-    Let,
-    If,
-    Def,
-    DefMacro,
+    SetBegin,
+    Discard,
+    VarQuote,
+    ReaderConditional,
+    ReaderConditionalSplicing,
+    TaggedLiteral,
     Keyword,
     MacroKeyword,
     Float,
@@ -26,7 +27,10 @@ pub enum TokenKind {
     Long,
     Hex,
     Binary,
+    Radix,
     BigNumber,
+    BigDecimal,
+    Ratio,
     UnicodeChar,
     String,
     Regexp,
@@ -73,7 +77,10 @@ trait Oneof<T> {
 pub struct Token<'a> {
     info: SourceInfo<'a>,
     value: i64,
-    kind: TokenKind
+    kind: TokenKind,
+    raw: i64,
+    leading_trivia: i64,
+    trailing_trivia: i64
 }
 
 
@@ -157,7 +164,10 @@ impl<'a> Token<'a> {
         Token {
             info  : info,
             value : -1,
-            kind  : kind
+            kind  : kind,
+            raw   : -1,
+            leading_trivia: -1,
+            trailing_trivia: -1
         }
     }
 
@@ -177,7 +187,10 @@ impl<'a> Token<'a> {
         Token {
             info  : info,
             value : -1,
-            kind  : TokenKind::from_char(token)
+            kind  : TokenKind::from_char(token),
+            raw   : -1,
+            leading_trivia: -1,
+            trailing_trivia: -1
         }
     }
     
@@ -185,7 +198,10 @@ impl<'a> Token<'a> {
         Token {
             info  : info,
             value : value,
-            kind  : kind
+            kind  : kind,
+            raw   : -1,
+            leading_trivia: -1,
+            trailing_trivia: -1
         }
     }
 
@@ -193,7 +209,10 @@ impl<'a> Token<'a> {
         Token {
             info: info,
             value: -1,
-            kind: TokenKind::Eof
+            kind: TokenKind::Eof,
+            raw: -1,
+            leading_trivia: -1,
+            trailing_trivia: -1
         }
     }
 
@@ -201,7 +220,10 @@ impl<'a> Token<'a> {
         Token {
             info  : info,
             value : -1,
-            kind  : TokenKind::Invalid
+            kind  : TokenKind::Invalid,
+            raw   : -1,
+            leading_trivia: -1,
+            trailing_trivia: -1
         }
     }
     
@@ -213,9 +235,28 @@ impl<'a> Token<'a> {
         self.info.line()
     }
 
-    pub fn info(&self) -> SourceInfo {
+    pub fn info(&self) -> SourceInfo<'a> {
         self.info
     }
+
+    /// Make the token span until the end of `info`.
+    pub fn extend(self, info: SourceInfo<'a>) -> Token<'a> {
+        Token {
+            info: self.info.extend(info),
+            ..self
+        }
+    }
+
+    /// Attach the source text and the surrounding trivia ids,
+    /// used by the trivia preserving scanner.
+    pub fn with_trivia(self, raw: i64, leading_trivia: i64, trailing_trivia: i64) -> Token<'a> {
+        Token {
+            raw: raw,
+            leading_trivia: leading_trivia,
+            trailing_trivia: trailing_trivia,
+            ..self
+        }
+    }
     
     pub fn kind(self) -> TokenKind {
         self.kind
@@ -225,6 +266,22 @@ impl<'a> Token<'a> {
         self.value
     }
 
+    /// Literal id of the source text of the token, -1 unless trivia is preserved.
+    pub fn raw(&self) -> i64 {
+        self.raw
+    }
+
+    /// Literal id of the whitespaces and comments before the token, -1 if none.
+    pub fn leading_trivia(&self) -> i64 {
+        self.leading_trivia
+    }
+
+    /// Literal id of the whitespaces and comments following the token
+    /// until the end of line, -1 if none.
+    pub fn trailing_trivia(&self) -> i64 {
+        self.trailing_trivia
+    }
+
     pub fn is(&self, kind: TokenKind) -> bool {
         self.kind == kind
     }